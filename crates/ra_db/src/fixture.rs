@@ -76,7 +76,7 @@ pub trait WithFixture: Default + SourceDatabaseExt + 'static {
         let mut db = Self::default();
         let (_, files) = with_files(&mut db, text);
         assert_eq!(files.len(), 1);
-        (db, files[0])
+        (db, files[0].0)
     }
 
     fn with_files(ra_fixture: &str) -> Self {
@@ -86,6 +86,15 @@ pub trait WithFixture: Default + SourceDatabaseExt + 'static {
         db
     }
 
+    /// Like `with_files`, but also returns the ids of the files by their path.
+    fn with_files_by_path(ra_fixture: &str) -> (Self, FxHashMap<String, FileId>) {
+        let mut db = Self::default();
+        let (pos, files) = with_files(&mut db, ra_fixture);
+        assert!(pos.is_none());
+        let files = files.into_iter().map(|(file_id, path)| (path, file_id)).collect();
+        (db, files)
+    }
+
     fn with_position(ra_fixture: &str) -> (Self, FilePosition) {
         let (db, file_id, range_or_offset) = Self::with_range_or_offset(ra_fixture);
         let offset = match range_or_offset {
@@ -116,7 +125,7 @@ impl<DB: SourceDatabaseExt + Default + 'static> WithFixture for DB {}
 fn with_files(
     db: &mut dyn SourceDatabaseExt,
     fixture: &str,
-) -> (Option<(FileId, RangeOrOffset)>, Vec<FileId>) {
+) -> (Option<(FileId, RangeOrOffset)>, Vec<(FileId, String)>) {
     let fixture = Fixture::parse(fixture);
 
     let mut files = Vec::new();
@@ -168,9 +177,9 @@ fn with_files(
 
         db.set_file_text(file_id, Arc::new(text));
         db.set_file_source_root(file_id, source_root_id);
-        let path = VfsPath::new_virtual_path(meta.path);
+        let path = VfsPath::new_virtual_path(meta.path.clone());
         file_set.insert(file_id, path.into());
-        files.push(file_id);
+        files.push((file_id, meta.path));
         file_id.0 += 1;
     }
 
@@ -14,12 +14,15 @@ pub use hir_expand::db::{
     MacroArgQuery, MacroDefQuery, MacroExpandQuery, ParseMacroQuery,
 };
 pub use hir_ty::db::{
-    AssociatedTyDataQuery, AssociatedTyValueQuery, CallableItemSignatureQuery, FieldTypesQuery,
-    GenericDefaultsQuery, GenericPredicatesForParamQuery, GenericPredicatesQuery, HirDatabase,
-    HirDatabaseStorage, ImplDatumQuery, ImplSelfTyQuery, ImplTraitQuery, InferQueryQuery,
-    InherentImplsInCrateQuery, InternAssocTyValueQuery, InternChalkImplQuery, InternTypeCtorQuery,
-    InternTypeParamIdQuery, ReturnTypeImplTraitsQuery, StructDatumQuery, TraitDatumQuery,
-    TraitImplsInCrateQuery, TraitImplsInDepsQuery, TraitSolveQuery, TyQuery, ValueTyQuery,
+    AllExplicitImplsInKrateQuery, AssociatedTyDataQuery, AssociatedTyValueQuery,
+    CallableItemSignatureQuery, ChalkSolverConfigQuery, FieldTypesQuery, GenericDefaultsQuery,
+    GenericPredicatesForParamQuery, GenericPredicatesQuery, HirDatabase, HirDatabaseStorage,
+    ImplDatumQuery, ImplSelfTyQuery, ImplTraitQuery, InferQueryQuery, InherentImplsInCrateQuery,
+    InternAssocTyValueQuery, InternChalkImplQuery, InternClosureQuery, InternLifetimeParamQuery,
+    InternTypeCtorQuery, InternTypeParamIdQuery, ObjectSafetyViolationsQuery, OpaqueTyDatumQuery,
+    ReturnTypeImplTraitsQuery, StructDatumQuery, TraitDatumQuery, TraitImplsInCrateQuery,
+    TraitImplsInDepsForQuery, TraitImplsInDepsQuery, TraitSolveQuery, TyQuery, TypeCtorFlagsQuery,
+    ValueTyQuery,
 };
 
 #[test]
@@ -9,11 +9,12 @@ use hir_expand::{
 };
 use ra_arena::{map::ArenaMap, Arena};
 use ra_syntax::ast::{self, NameOwner, TypeAscriptionOwner, VisibilityOwner};
+use tt::{Leaf, Subtree, TokenTree};
 
 use crate::{
     body::{CfgExpander, LowerCtx},
     db::DefDatabase,
-    item_tree::{Field, Fields, ItemTree},
+    item_tree::{AttrOwner, Field, Fields, ItemTree, ModItem},
     src::HasChildSource,
     src::HasSource,
     trace::Trace,
@@ -29,12 +30,21 @@ use ra_cfg::CfgOptions;
 pub struct StructData {
     pub name: Name,
     pub variant_data: Arc<VariantData>,
+    pub repr: ReprOptions,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EnumData {
     pub name: Name,
     pub variants: Arena<EnumVariantData>,
+    pub repr: ReprOptions,
+}
+
+/// The layout-relevant parts of an ADT's `#[repr(...)]` attributes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReprOptions {
+    pub c: bool,
+    pub packed: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -66,8 +76,13 @@ impl StructData {
 
         let strukt = &item_tree[loc.id.value];
         let variant_data = lower_fields(&item_tree, &cfg_options, &strukt.fields);
+        let repr = repr_from_attrs(&item_tree, ModItem::from(loc.id.value).into());
 
-        Arc::new(StructData { name: strukt.name.clone(), variant_data: Arc::new(variant_data) })
+        Arc::new(StructData {
+            name: strukt.name.clone(),
+            variant_data: Arc::new(variant_data),
+            repr,
+        })
     }
     pub(crate) fn union_data_query(db: &dyn DefDatabase, id: UnionId) -> Arc<StructData> {
         let loc = id.lookup(db);
@@ -76,8 +91,34 @@ impl StructData {
 
         let union = &item_tree[loc.id.value];
         let variant_data = lower_fields(&item_tree, &cfg_options, &union.fields);
+        let repr = repr_from_attrs(&item_tree, ModItem::from(loc.id.value).into());
+
+        Arc::new(StructData {
+            name: union.name.clone(),
+            variant_data: Arc::new(variant_data),
+            repr,
+        })
+    }
+}
 
-        Arc::new(StructData { name: union.name.clone(), variant_data: Arc::new(variant_data) })
+fn repr_from_attrs(item_tree: &ItemTree, owner: AttrOwner) -> ReprOptions {
+    let mut repr = ReprOptions::default();
+    for tt in item_tree.attrs(owner).by_key("repr").tt_values() {
+        parse_repr_tt(tt, &mut repr);
+    }
+    repr
+}
+
+fn parse_repr_tt(tt: &Subtree, repr: &mut ReprOptions) {
+    // `#[repr(C, packed(2))]` and friends; only the top-level names matter here
+    for tt in tt.token_trees.iter() {
+        if let TokenTree::Leaf(Leaf::Ident(ident)) = tt {
+            match ident.text.as_str() {
+                "C" => repr.c = true,
+                "packed" => repr.packed = true,
+                _ => {}
+            }
+        }
     }
 }
 
@@ -101,7 +142,9 @@ impl EnumData {
             }
         }
 
-        Arc::new(EnumData { name: enum_.name.clone(), variants })
+        let repr = repr_from_attrs(&item_tree, ModItem::from(loc.id.value).into());
+
+        Arc::new(EnumData { name: enum_.name.clone(), variants, repr })
     }
 
     pub fn variant(&self, name: &Name) -> Option<LocalEnumVariantId> {
@@ -39,7 +39,7 @@ use ast::AstChildren;
 use rustc_hash::FxHashMap;
 use std::{any::type_name, sync::Arc};
 
-pub(crate) struct LowerCtx {
+pub struct LowerCtx {
     hygiene: Hygiene,
 }
 
@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 use hir_expand::{name::Name, InFile};
 use ra_prof::profile;
-use ra_syntax::ast;
+use ra_syntax::{ast, SmolStr};
 
 use crate::{
     attr::Attrs,
@@ -27,6 +27,9 @@ pub struct FunctionData {
     /// can be called as a method.
     pub has_self_param: bool,
     pub is_unsafe: bool,
+    pub is_varargs: bool,
+    /// `None` means the Rust ABI.
+    pub abi: Option<SmolStr>,
     pub visibility: RawVisibility,
 }
 
@@ -43,6 +46,8 @@ impl FunctionData {
             attrs: item_tree.attrs(ModItem::from(loc.id.value).into()).clone(),
             has_self_param: func.has_self_param,
             is_unsafe: func.is_unsafe,
+            is_varargs: func.is_varargs,
+            abi: func.abi.clone(),
             visibility: item_tree[func.visibility].clone(),
         })
     }
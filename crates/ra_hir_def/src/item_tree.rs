@@ -22,7 +22,7 @@ use hir_expand::{
     HirFileId, InFile,
 };
 use ra_arena::{Arena, Idx, RawId};
-use ra_syntax::{ast, match_ast};
+use ra_syntax::{ast, match_ast, SmolStr};
 use rustc_hash::FxHashMap;
 use smallvec::SmallVec;
 use test_utils::mark;
@@ -502,6 +502,12 @@ pub struct Function {
     pub generic_params: GenericParamsId,
     pub has_self_param: bool,
     pub is_unsafe: bool,
+    /// Whether the parameter list ends in a C-variadic `...`, which isn't
+    /// part of `params`.
+    pub is_varargs: bool,
+    /// The ABI given by `extern "abi"`, either on the function itself or on
+    /// the extern block it is declared in. `None` means the Rust ABI.
+    pub abi: Option<SmolStr>,
     pub params: Box<[TypeRef]>,
     pub ret_type: TypeRef,
     pub ast_id: FileAstId<ast::FnDef>,
@@ -9,7 +9,7 @@ use hir_expand::{ast_id_map::AstIdMap, hygiene::Hygiene, HirFileId};
 use ra_arena::map::ArenaMap;
 use ra_syntax::{
     ast::{self, ModuleItemOwner},
-    SyntaxNode,
+    SyntaxKind, SyntaxNode,
 };
 use smallvec::SmallVec;
 use std::{collections::hash_map::Entry, mem, sync::Arc};
@@ -289,6 +289,7 @@ impl Ctx {
 
         let mut params = Vec::new();
         let mut has_self_param = false;
+        let mut is_varargs = false;
         if let Some(param_list) = func.param_list() {
             if let Some(self_param) = param_list.self_param() {
                 let self_type = match self_param.ascribed_type() {
@@ -310,6 +311,10 @@ impl Ctx {
                 has_self_param = true;
             }
             for param in param_list.params() {
+                if param.dotdotdot_token().is_some() {
+                    is_varargs = true;
+                    continue;
+                }
                 let type_ref = TypeRef::from_ast_opt(&self.body_ctx, param.ascribed_type());
                 params.push(type_ref);
             }
@@ -334,6 +339,8 @@ impl Ctx {
             generic_params: GenericParamsId::EMPTY,
             has_self_param,
             is_unsafe: func.unsafe_token().is_some(),
+            is_varargs,
+            abi: func.abi().map(|abi| lower_abi(&abi)),
             params: params.into_boxed_slice(),
             ret_type,
             ast_id,
@@ -538,6 +545,7 @@ impl Ctx {
     }
 
     fn lower_extern_block(&mut self, block: &ast::ExternBlock) -> Vec<ModItem> {
+        let abi = block.abi().map(|abi| lower_abi(&abi));
         block.extern_item_list().map_or(Vec::new(), |list| {
             list.extern_items()
                 .filter_map(|item| {
@@ -546,7 +554,9 @@ impl Ctx {
                     let id: ModItem = match item {
                         ast::ExternItem::FnDef(ast) => {
                             let func = self.lower_function(&ast)?;
-                            self.data().functions[func.index].is_unsafe = true;
+                            let data = &mut self.data().functions[func.index];
+                            data.is_unsafe = true;
+                            data.abi = abi.clone();
                             func.into()
                         }
                         ast::ExternItem::StaticDef(ast) => {
@@ -698,3 +708,13 @@ enum GenericsOwner<'a> {
     TypeAlias,
     Impl,
 }
+
+/// The ABI named by `extern "abi"`. A plain `extern` means the C ABI.
+fn lower_abi(abi: &ast::Abi) -> SmolStr {
+    abi.syntax()
+        .children_with_tokens()
+        .filter_map(|it| it.into_token())
+        .find(|it| it.kind() == SyntaxKind::STRING || it.kind() == SyntaxKind::RAW_STRING)
+        .map(|it| it.text().trim_start_matches('r').trim_matches('#').trim_matches('"').into())
+        .unwrap_or_else(|| "C".into())
+}
@@ -123,7 +123,7 @@ fn deref_by_trait(
                 kinds: vars.0.kinds.clone(),
             })
         }
-        Solution::Ambig(_) => {
+        Solution::Ambig(_) | Solution::Overflow => {
             info!("Ambiguous solution for derefing {:?}: {:?}", ty.value, solution);
             None
         }
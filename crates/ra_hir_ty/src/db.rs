@@ -3,8 +3,8 @@
 use std::sync::Arc;
 
 use hir_def::{
-    db::DefDatabase, DefWithBodyId, FunctionId, GenericDefId, ImplId, LocalFieldId, TypeParamId,
-    VariantId,
+    db::DefDatabase, expr::ExprId, DefWithBodyId, FunctionId, GenericDefId, ImplId, LocalFieldId,
+    TraitId, TypeParamId, VariantId,
 };
 use ra_arena::map::ArenaMap;
 use ra_db::{impl_intern_key, salsa, CrateId, Upcast};
@@ -12,14 +12,18 @@ use ra_prof::profile;
 
 use crate::{
     method_resolution::{InherentImpls, TraitImpls},
-    traits::{chalk, AssocTyValue, Impl},
+    traits::{chalk, AssocTyValue, ChalkSolverConfig, Impl},
     Binders, CallableDef, GenericPredicate, InferenceResult, OpaqueTyId, PolyFnSig,
-    ReturnTypeImplTraits, TraitRef, Ty, TyDefId, TypeCtor, ValueTyDefId,
+    ReturnTypeImplTraits, TraitRef, Ty, TyDefId, TypeCtor, TypeCtorFlags, ValueTyDefId,
 };
 use hir_expand::name::Name;
 
 #[salsa::query_group(HirDatabaseStorage)]
 pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
+    /// How much work the trait solver may do for a single goal.
+    #[salsa::input]
+    fn chalk_solver_config(&self) -> ChalkSolverConfig;
+
     #[salsa::invoke(infer_wait)]
     #[salsa::transparent]
     fn infer(&self, def: DefWithBodyId) -> Arc<InferenceResult>;
@@ -66,6 +70,9 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
     #[salsa::invoke(crate::lower::generic_defaults_query)]
     fn generic_defaults(&self, def: GenericDefId) -> Arc<[Binders<Ty>]>;
 
+    #[salsa::invoke(crate::type_ctor_flags_query)]
+    fn type_ctor_flags(&self, krate: CrateId, type_ctor: TypeCtor) -> TypeCtorFlags;
+
     #[salsa::invoke(InherentImpls::inherent_impls_in_crate_query)]
     fn inherent_impls_in_crate(&self, krate: CrateId) -> Arc<InherentImpls>;
 
@@ -75,6 +82,12 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
     #[salsa::invoke(TraitImpls::trait_impls_in_deps_query)]
     fn trait_impls_in_deps(&self, krate: CrateId) -> Arc<TraitImpls>;
 
+    #[salsa::invoke(TraitImpls::trait_impls_in_deps_for_query)]
+    fn trait_impls_in_deps_for(&self, krate: CrateId, trait_: TraitId) -> Arc<TraitImpls>;
+
+    #[salsa::invoke(crate::method_resolution::all_explicit_impls_in_krate_query)]
+    fn all_explicit_impls_in_krate(&self, krate: CrateId) -> Arc<[ImplId]>;
+
     // Interned IDs for Chalk integration
     #[salsa::interned]
     fn intern_type_ctor(&self, type_ctor: TypeCtor) -> crate::TypeCtorId;
@@ -85,6 +98,13 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
     #[salsa::interned]
     fn intern_impl_trait_id(&self, id: OpaqueTyId) -> InternedOpaqueTyId;
     #[salsa::interned]
+    fn intern_lifetime_param(&self, param: (GenericDefId, Name)) -> InternedLifetimeParam;
+    #[salsa::interned]
+    fn intern_closure(&self, id: (DefWithBodyId, ExprId)) -> InternedClosureId;
+    /// Maps impls to Chalk's `ImplId`s and back. Interning guarantees that
+    /// each `Impl` gets exactly one id, so this doubles as the lookup table
+    /// from Chalk impls to their `hir_def::ImplId`s.
+    #[salsa::interned]
     fn intern_chalk_impl(&self, impl_: Impl) -> crate::traits::GlobalImplId;
     #[salsa::interned]
     fn intern_assoc_ty_value(&self, assoc_ty_value: AssocTyValue) -> crate::traits::AssocTyValueId;
@@ -93,17 +113,22 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
     fn associated_ty_data(&self, id: chalk::AssocTypeId) -> Arc<chalk::AssociatedTyDatum>;
 
     #[salsa::invoke(chalk::trait_datum_query)]
+    #[salsa::cycle(chalk::trait_datum_recover)]
     fn trait_datum(&self, krate: CrateId, trait_id: chalk::TraitId) -> Arc<chalk::TraitDatum>;
 
     #[salsa::invoke(chalk::struct_datum_query)]
     fn struct_datum(&self, krate: CrateId, struct_id: chalk::AdtId) -> Arc<chalk::StructDatum>;
 
     #[salsa::invoke(crate::traits::chalk::impl_datum_query)]
+    #[salsa::cycle(crate::traits::chalk::impl_datum_recover)]
     fn impl_datum(&self, krate: CrateId, impl_id: chalk::ImplId) -> Arc<chalk::ImplDatum>;
 
     #[salsa::invoke(crate::traits::chalk::fn_def_datum_query)]
     fn fn_def_datum(&self, krate: CrateId, fn_def_id: chalk::FnDefId) -> Arc<chalk::FnDefDatum>;
 
+    #[salsa::invoke(crate::traits::chalk::opaque_ty_datum_query)]
+    fn opaque_ty_datum(&self, id: chalk::OpaqueTyId) -> Arc<chalk::OpaqueTyDatum>;
+
     #[salsa::invoke(crate::traits::chalk::associated_ty_value_query)]
     fn associated_ty_value(
         &self,
@@ -111,6 +136,15 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
         id: chalk::AssociatedTyValueId,
     ) -> Arc<chalk::AssociatedTyValue>;
 
+    #[salsa::invoke(crate::traits::object_safety::object_safety_violations_query)]
+    fn object_safety_violations(
+        &self,
+        trait_: TraitId,
+    ) -> Arc<[crate::traits::ObjectSafetyViolation]>;
+
+    /// Solutions are memoized by salsa across revisions: Chalk only gets at
+    /// impls and traits through queries, so a solution is only recomputed
+    /// when something it looked at actually changed.
     #[salsa::invoke(crate::traits::trait_solve_query)]
     fn trait_solve(
         &self,
@@ -151,3 +185,11 @@ impl_intern_key!(GlobalTypeParamId);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct InternedOpaqueTyId(salsa::InternId);
 impl_intern_key!(InternedOpaqueTyId);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InternedLifetimeParam(salsa::InternId);
+impl_intern_key!(InternedLifetimeParam);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InternedClosureId(salsa::InternId);
+impl_intern_key!(InternedClosureId);
@@ -215,6 +215,36 @@ impl AstDiagnostic for MissingUnsafe {
     }
 }
 
+#[derive(Debug)]
+pub struct OpaqueTypeMismatch {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Diagnostic for OpaqueTypeMismatch {
+    fn message(&self) -> String {
+        format!("expected {}, found opaque type {}", self.expected, self.actual)
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for OpaqueTypeMismatch {
+    type AST = ast::Expr;
+
+    fn ast(&self, db: &dyn AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        let node = self.source().value.to_node(&root);
+        ast::Expr::cast(node).unwrap()
+    }
+}
+
 #[derive(Debug)]
 pub struct MismatchedArgCount {
     pub file: HirFileId,
@@ -12,7 +12,9 @@ use crate::{
     diagnostics::{
         match_check::{is_useful, MatchCheckCtx, Matrix, PatStack, Usefulness},
         MismatchedArgCount, MissingFields, MissingMatchArms, MissingOkInTailExpr, MissingPatFields,
+        OpaqueTypeMismatch,
     },
+    display::HirDisplay,
     utils::variant_data,
     ApplicationTy, InferenceResult, Ty, TypeCtor,
 };
@@ -86,6 +88,7 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         if let Expr::Block { tail: Some(t), .. } = body_expr {
             self.validate_results_in_tail_expr(body.body_expr, *t, db);
         }
+        self.validate_opaque_type_mismatches(db);
     }
 
     fn create_record_literal_missing_fields_diagnostic(
@@ -280,6 +283,32 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         }
     }
 
+    /// Outside of its defining function, the concrete type behind an
+    /// `impl Trait` must not be observable, so using a value of the opaque type
+    /// where some concrete type is expected is an error.
+    fn validate_opaque_type_mismatches(&mut self, db: &dyn HirDatabase) {
+        if self.infer.type_mismatches.iter().next().is_none() {
+            return;
+        }
+        let (_, source_map) = db.body_with_source_map(self.owner.into());
+        for (id, mismatch) in self.infer.type_mismatches.iter() {
+            if !is_opaque(&mismatch.actual)
+                || is_opaque(&mismatch.expected)
+                || mismatch.expected == Ty::Unknown
+            {
+                continue;
+            }
+            if let Ok(source_ptr) = source_map.expr_syntax(id) {
+                self.sink.push(OpaqueTypeMismatch {
+                    file: source_ptr.file_id,
+                    expr: source_ptr.value,
+                    expected: mismatch.expected.display(db).to_string(),
+                    actual: mismatch.actual.display(db).to_string(),
+                });
+            }
+        }
+    }
+
     fn validate_results_in_tail_expr(&mut self, body_id: ExprId, id: ExprId, db: &dyn HirDatabase) {
         // the mismatch will be on the whole block currently
         let mismatch = match self.infer.type_mismatch_for_expr(body_id) {
@@ -314,6 +343,10 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
     }
 }
 
+fn is_opaque(ty: &Ty) -> bool {
+    matches!(ty, Ty::Opaque(_) | Ty::Apply(ApplicationTy { ctor: TypeCtor::OpaqueType(_), .. }))
+}
+
 pub fn record_literal_missing_fields(
     db: &dyn HirDatabase,
     infer: &InferenceResult,
@@ -379,7 +412,10 @@ mod tests {
     use expect::{expect, Expect};
     use ra_db::fixture::WithFixture;
 
-    use crate::{diagnostics::MismatchedArgCount, test_db::TestDB};
+    use crate::{
+        diagnostics::{MismatchedArgCount, OpaqueTypeMismatch},
+        test_db::TestDB,
+    };
 
     fn check_diagnostic(ra_fixture: &str, expect: Expect) {
         let msg = TestDB::with_single_file(ra_fixture).0.diagnostic::<MismatchedArgCount>().0;
@@ -518,4 +554,24 @@ mod tests {
             expect![["\"En::Variant(0)\": Expected 2 arguments, found 1\n"]],
         )
     }
+
+    #[test]
+    fn opaque_type_mismatch() {
+        let (msg, count) = TestDB::with_single_file(
+            r"
+            trait Display {}
+            impl Display for u32 {}
+            fn foo() -> impl Display { 1u32 }
+            fn f() {
+                let x: u32 = foo();
+                let y = foo();
+            }
+            ",
+        )
+        .0
+        .diagnostic::<OpaqueTypeMismatch>();
+
+        assert_eq!(count, 1);
+        assert_eq!(msg, "\"foo()\": expected u32, found opaque type impl Display\n");
+    }
 }
@@ -1,12 +1,12 @@
-//! Provides validations for unsafe code. Currently checks if unsafe functions are missing
-//! unsafe blocks.
+//! Provides validations for unsafe code. Currently checks if calls to unsafe functions,
+//! raw pointer dereferences and union field accesses are missing unsafe blocks.
 
 use std::sync::Arc;
 
 use hir_def::{
     body::Body,
-    expr::{Expr, ExprId, UnaryOp},
-    DefWithBodyId,
+    expr::{BinaryOp, Expr, ExprId, UnaryOp},
+    DefWithBodyId, FunctionId, VariantId,
 };
 use hir_expand::diagnostics::DiagnosticSink;
 
@@ -75,6 +75,17 @@ pub fn unsafe_expressions(
     unsafe_exprs
 }
 
+/// Functions with `#[target_feature]` are unsafe to call even if they aren't
+/// declared `unsafe`, since the CPU might not support the feature.
+fn is_unsafe_to_call(db: &dyn HirDatabase, func: FunctionId) -> bool {
+    let data = db.function_data(func);
+    data.is_unsafe || data.attrs.by_key("target_feature").exists()
+}
+
+fn is_union_field(infer: &InferenceResult, expr: ExprId) -> bool {
+    matches!(infer.field_resolution(expr).map(|f| f.parent), Some(VariantId::UnionId(_)))
+}
+
 fn walk_unsafe(
     unsafe_exprs: &mut Vec<UnsafeExpr>,
     db: &dyn HirDatabase,
@@ -92,7 +103,7 @@ fn walk_unsafe(
                 ..
             }) = ty
             {
-                if db.function_data(func).is_unsafe {
+                if is_unsafe_to_call(db, func) {
                     unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
                 }
             }
@@ -100,7 +111,7 @@ fn walk_unsafe(
         Expr::MethodCall { .. } => {
             if infer
                 .method_resolution(current)
-                .map(|func| db.function_data(func).is_unsafe)
+                .map(|func| is_unsafe_to_call(db, func))
                 .unwrap_or(false)
             {
                 unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
@@ -111,6 +122,21 @@ fn walk_unsafe(
                 unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
             }
         }
+        Expr::Field { .. } => {
+            if is_union_field(infer, current) {
+                unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
+            }
+        }
+        Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::Assignment { op: None }) } => {
+            // assigning to a union field is safe, only reading from one isn't
+            if let Expr::Field { expr: receiver, .. } = &body.exprs[*lhs] {
+                if is_union_field(infer, *lhs) {
+                    walk_unsafe(unsafe_exprs, db, infer, body, *receiver, inside_unsafe_block);
+                    walk_unsafe(unsafe_exprs, db, infer, body, *rhs, inside_unsafe_block);
+                    return;
+                }
+            }
+        }
         Expr::Unsafe { body: child } => {
             return walk_unsafe(unsafe_exprs, db, infer, body, *child, true);
         }
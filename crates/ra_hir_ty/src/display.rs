@@ -3,8 +3,11 @@
 use std::fmt;
 
 use crate::{
-    db::HirDatabase, utils::generics, ApplicationTy, CallableDef, FnSig, GenericPredicate,
-    Obligation, OpaqueTyId, ProjectionTy, Substs, TraitRef, Ty, TypeCtor,
+    db::HirDatabase,
+    traits::chalk::{from_chalk, Interner},
+    utils::generics,
+    ApplicationTy, CallableDef, FnSig, GenericPredicate, Lifetime, Obligation, ProjectionTy,
+    Substs, TraitRef, Ty, TypeCtor,
 };
 use hir_def::{
     find_path, generics::TypeParamProvenance, item_scope::ItemInNs, AdtId, AssocContainerId,
@@ -362,16 +365,10 @@ impl HirDisplay for ApplicationTy {
                 }
             }
             TypeCtor::OpaqueType(opaque_ty_id) => {
-                let bounds = match opaque_ty_id {
-                    OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
-                        let datas =
-                            f.db.return_type_impl_traits(func).expect("impl trait id without data");
-                        let data = (*datas)
-                            .as_ref()
-                            .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
-                        data.subst(&self.parameters)
-                    }
-                };
+                let bounds = opaque_ty_id
+                    .bounds(f.db)
+                    .expect("impl trait id without data")
+                    .subst(&self.parameters);
                 write!(f, "impl ")?;
                 write_bounds_like_dyn_trait(&bounds.value, f)?;
                 // FIXME: it would maybe be good to distinguish this from the alias type (when debug printing), and to show the substitution
@@ -404,13 +401,9 @@ impl HirDisplay for ProjectionTy {
             return write!(f, "{}", TYPE_HINT_TRUNCATION);
         }
 
-        let trait_ = f.db.trait_data(self.trait_(f.db));
-        write!(f, "<{} as {}", self.parameters[0].display(f.db), trait_.name)?;
-        if self.parameters.len() > 1 {
-            write!(f, "<")?;
-            f.write_joined(&self.parameters[1..], ", ")?;
-            write!(f, ">")?;
-        }
+        // `<SelfType as Trait<Args>>::AssocType`
+        write!(f, "<")?;
+        self.trait_ref(f.db).hir_fmt_ext(f, true)?;
         write!(f, ">::{}", f.db.type_alias_data(self.associated_ty).name)?;
         Ok(())
     }
@@ -449,16 +442,11 @@ impl HirDisplay for Ty {
                 write_bounds_like_dyn_trait(predicates, f)?;
             }
             Ty::Opaque(opaque_ty) => {
-                let bounds = match opaque_ty.opaque_ty_id {
-                    OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
-                        let datas =
-                            f.db.return_type_impl_traits(func).expect("impl trait id without data");
-                        let data = (*datas)
-                            .as_ref()
-                            .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
-                        data.subst(&opaque_ty.parameters)
-                    }
-                };
+                let bounds = opaque_ty
+                    .opaque_ty_id
+                    .bounds(f.db)
+                    .expect("impl trait id without data")
+                    .subst(&opaque_ty.parameters);
                 write!(f, "impl ")?;
                 write_bounds_like_dyn_trait(&bounds.value, f)?;
             }
@@ -576,14 +564,8 @@ impl HirDisplay for GenericPredicate {
         match self {
             GenericPredicate::Implemented(trait_ref) => trait_ref.hir_fmt(f)?,
             GenericPredicate::Projection(projection_pred) => {
-                write!(f, "<")?;
-                projection_pred.projection_ty.trait_ref(f.db).hir_fmt_ext(f, true)?;
-                write!(
-                    f,
-                    ">::{} = {}",
-                    f.db.type_alias_data(projection_pred.projection_ty.associated_ty).name,
-                    projection_pred.ty.display(f.db)
-                )?;
+                projection_pred.projection_ty.hir_fmt(f)?;
+                write!(f, " = {}", projection_pred.ty.display(f.db))?;
             }
             GenericPredicate::Error => write!(f, "{{error}}")?,
         }
@@ -604,3 +586,35 @@ impl HirDisplay for Obligation {
         })
     }
 }
+
+impl HirDisplay for chalk_ir::Substitution<Interner> {
+    fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
+        f.write_joined(self.iter(&Interner), ", ")
+    }
+}
+
+impl HirDisplay for &chalk_ir::GenericArg<Interner> {
+    fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
+        HirDisplay::hir_fmt(*self, f)
+    }
+}
+
+impl HirDisplay for chalk_ir::GenericArg<Interner> {
+    fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
+        match self.data(&Interner) {
+            chalk_ir::GenericArgData::Ty(ty) => {
+                let ty: Ty = from_chalk(f.db, ty.clone());
+                ty.hir_fmt(f)
+            }
+            chalk_ir::GenericArgData::Lifetime(lifetime) => {
+                match from_chalk(f.db, lifetime.clone()) {
+                    Lifetime::Static => write!(f, "'static"),
+                    Lifetime::Named(_, name) => write!(f, "{}", name),
+                    Lifetime::Anonymous | Lifetime::Bound(_) => write!(f, "'_"),
+                }
+            }
+            // we don't keep track of array lengths, same as for `[T; _]`
+            chalk_ir::GenericArgData::Const(_) => write!(f, "_"),
+        }
+    }
+}
@@ -14,6 +14,7 @@
 //! the `ena` crate, which is extracted from rustc.
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::mem;
 use std::ops::Index;
 use std::sync::Arc;
@@ -38,7 +39,7 @@ use stdx::impl_from;
 
 use super::{
     primitive::{FloatTy, IntTy},
-    traits::{Guidance, Obligation, ProjectionPredicate, Solution},
+    traits::{primitive_op_output, Guidance, Obligation, ProjectionPredicate, Solution},
     InEnvironment, ProjectionTy, Substs, TraitEnvironment, TraitRef, Ty, TypeCtor, TypeWalk,
 };
 use crate::{
@@ -65,6 +66,7 @@ mod coerce;
 /// The entry point of type inference.
 pub(crate) fn infer_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Arc<InferenceResult> {
     let _p = profile("infer_query");
+    let _inferring = InferringGuard::new(def);
     let resolver = def.resolver(db.upcast());
     let mut ctx = InferenceContext::new(db, def, resolver);
 
@@ -79,6 +81,35 @@ pub(crate) fn infer_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Arc<Infer
     Arc::new(ctx.resolve_all())
 }
 
+thread_local! {
+    /// The bodies whose inference is running on this thread right now.
+    static INFERRING: RefCell<Vec<DefWithBodyId>> = RefCell::new(Vec::new());
+}
+
+/// Whether `def` is being inferred on this thread right now. Chalk asks about
+/// the closures in a body (and the opaque types it defines) while solving
+/// goals from its inference, and the answers can't depend on the result of
+/// that inference without a query cycle, so they need to do without it.
+pub(crate) fn is_being_inferred(def: DefWithBodyId) -> bool {
+    INFERRING.with(|inferring| inferring.borrow().contains(&def))
+}
+
+/// Marks a body as being inferred until dropped, see `is_being_inferred`.
+struct InferringGuard;
+
+impl InferringGuard {
+    fn new(def: DefWithBodyId) -> InferringGuard {
+        INFERRING.with(|inferring| inferring.borrow_mut().push(def));
+        InferringGuard
+    }
+}
+
+impl Drop for InferringGuard {
+    fn drop(&mut self) {
+        INFERRING.with(|inferring| inferring.borrow_mut().pop());
+    }
+}
+
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 enum ExprOrPatId {
     ExprId(ExprId),
@@ -412,6 +443,10 @@ impl<'a> InferenceContext<'a> {
     }
 
     fn normalize_projection_ty(&mut self, proj_ty: ProjectionTy) -> Ty {
+        let krate = self.resolver.krate();
+        if let Some(ty) = krate.and_then(|krate| primitive_op_output(self.db, krate, &proj_ty)) {
+            return ty;
+        }
         let var = self.table.new_type_var();
         let predicate = ProjectionPredicate { projection_ty: proj_ty, ty: var.clone() };
         let obligation = Obligation::Projection(predicate);
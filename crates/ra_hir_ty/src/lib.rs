@@ -28,10 +28,12 @@ use std::{iter, mem, ops::Deref, sync::Arc};
 
 use hir_def::{
     expr::ExprId,
+    lang_item::lang_attr,
     type_ref::{Mutability, Rawness},
     AdtId, AssocContainerId, DefWithBodyId, GenericDefId, HasModule, Lookup, TraitId, TypeAliasId,
     TypeParamId,
 };
+use hir_expand::name::Name;
 use itertools::Itertools;
 use ra_db::{impl_intern_key, salsa, CrateId};
 
@@ -47,7 +49,7 @@ pub use infer::{InferTy, InferenceResult};
 pub use lower::CallableDef;
 pub use lower::{
     associated_type_shorthand_candidates, callable_item_sig, ImplTraitLoweringMode, TyDefId,
-    TyLoweringContext, ValueTyDefId,
+    TyLoweringContext, TyParseError, ValueTyDefId,
 };
 pub use traits::{InEnvironment, Obligation, ProjectionPredicate, TraitEnvironment};
 
@@ -180,12 +182,11 @@ impl TypeCtor {
                 generic_params.len()
             }
             TypeCtor::OpaqueType(opaque_ty_id) => {
-                match opaque_ty_id {
-                    OpaqueTyId::ReturnTypeImplTrait(func, _) => {
-                        let generic_params = generics(db.upcast(), func.into());
-                        generic_params.len()
-                    }
-                }
+                let def: GenericDefId = match opaque_ty_id {
+                    OpaqueTyId::ReturnTypeImplTrait(func, _) => func.into(),
+                    OpaqueTyId::AssocTyImplTrait(type_alias) => type_alias.into(),
+                };
+                generics(db.upcast(), def).len()
             }
             TypeCtor::FnPtr { num_args } => num_args as usize + 1,
             TypeCtor::Tuple { cardinality } => cardinality as usize,
@@ -217,6 +218,9 @@ impl TypeCtor {
                 OpaqueTyId::ReturnTypeImplTrait(func, _) => {
                     Some(func.lookup(db.upcast()).module(db.upcast()).krate)
                 }
+                OpaqueTyId::AssocTyImplTrait(type_alias) => {
+                    Some(type_alias.lookup(db.upcast()).module(db.upcast()).krate)
+                }
             },
         }
     }
@@ -244,6 +248,51 @@ impl TypeCtor {
     }
 }
 
+/// Various boolean properties of a `TypeCtor`, as seen from a certain crate.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct TypeCtorFlags {
+    /// Whether the type is defined in a crate other than the current one.
+    pub upstream: bool,
+    /// Whether the type is marked `#[fundamental]`, like `Box` or `&T`.
+    pub fundamental: bool,
+    /// Whether the type is `PhantomData`.
+    pub phantom_data: bool,
+    /// Whether the type is an `extern type`.
+    pub extern_type: bool,
+    /// Whether values of the type have a statically known size, i.e. whether
+    /// it implements `Sized` no matter what its parameters are.
+    pub is_sized: bool,
+}
+
+pub(crate) fn type_ctor_flags_query(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    type_ctor: TypeCtor,
+) -> TypeCtorFlags {
+    let upstream = type_ctor.krate(db) != Some(krate);
+    let (fundamental, phantom_data) = match type_ctor {
+        TypeCtor::Adt(adt) => {
+            let fundamental = db.attrs(adt.into()).by_key("fundamental").exists();
+            let phantom_data =
+                lang_attr(db.upcast(), adt).map_or(false, |name| name == "phantom_data");
+            (fundamental, phantom_data)
+        }
+        TypeCtor::Ref(_) => (true, false),
+        _ => (false, false),
+    };
+    TypeCtorFlags {
+        upstream,
+        fundamental,
+        phantom_data,
+        // FIXME: `type Foo;` in an `extern` block is lowered like a type alias
+        // without a type, so there is no `TypeCtor` for extern types yet. Once
+        // there is, it should set this and clear `is_sized`.
+        extern_type: false,
+        // FIXME: ADTs with an unsized last field are unsized as well
+        is_sized: !matches!(type_ctor, TypeCtor::Str | TypeCtor::Slice),
+    }
+}
+
 /// A nominal type with (maybe 0) type parameters. This might be a primitive
 /// type like `bool`, a struct, tuple, function pointer, reference or
 /// several other things.
@@ -351,6 +400,24 @@ pub enum Ty {
     Unknown,
 }
 
+/// A lifetime. We don't do any borrow checking, so lifetimes are mostly
+/// ignored; but Chalk represents them, so they need to survive the conversion
+/// to Chalk and back.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Lifetime {
+    /// The `'static` lifetime.
+    Static,
+    /// An anonymous lifetime, i.e. `'_` or an elided lifetime.
+    Anonymous,
+    /// A named lifetime parameter like `'a`, together with the item declaring
+    /// it; `'a` on two different items are different lifetimes.
+    Named(GenericDefId, Name),
+    /// A lifetime bound by an enclosing binder, e.g. the `'a` in
+    /// `for<'a> Fn(&'a u32)`. Like for `Ty::Bound`, this uses De Bruijn
+    /// indices.
+    Bound(BoundVar),
+}
+
 /// A list of substitutions for generic parameters.
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct Substs(Arc<[Ty]>);
@@ -408,6 +475,12 @@ impl Substs {
         Substs::type_params_for_generics(&params)
     }
 
+    /// Return Substs that replace each parameter by a bound variable of the
+    /// innermost binder.
+    pub(crate) fn for_generics(generic_params: &Generics) -> Substs {
+        Substs::bound_vars(generic_params, DebruijnIndex::INNERMOST)
+    }
+
     /// Return Substs that replace each parameter by a bound variable.
     pub(crate) fn bound_vars(generic_params: &Generics, debruijn: DebruijnIndex) -> Substs {
         Substs(
@@ -802,6 +875,20 @@ impl Ty {
         }
     }
 
+    /// Returns all type constructors mentioned in this type, including the
+    /// ones nested in its parameters, without duplicates.
+    pub fn collect_tyconstrs(&self) -> Vec<TypeCtor> {
+        let mut ctors = Vec::new();
+        self.walk(&mut |ty| {
+            if let Ty::Apply(a_ty) = ty {
+                if !ctors.contains(&a_ty.ctor) {
+                    ctors.push(a_ty.ctor);
+                }
+            }
+        });
+        ctors
+    }
+
     /// If this is a `dyn Trait`, returns that trait.
     pub fn dyn_trait(&self) -> Option<TraitId> {
         self.dyn_trait_ref().map(|it| it.trait_)
@@ -862,18 +949,8 @@ impl Ty {
     pub fn impl_trait_bounds(&self, db: &dyn HirDatabase) -> Option<Vec<GenericPredicate>> {
         match self {
             Ty::Opaque(opaque_ty) => {
-                let predicates = match opaque_ty.opaque_ty_id {
-                    OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
-                        db.return_type_impl_traits(func).map(|it| {
-                            let data = (*it)
-                                .as_ref()
-                                .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
-                            data.subst(&opaque_ty.parameters)
-                        })
-                    }
-                };
-
-                predicates.map(|it| it.value)
+                let predicates = opaque_ty.opaque_ty_id.bounds(db);
+                predicates.map(|it| it.subst(&opaque_ty.parameters).value)
             }
             Ty::Placeholder(id) => {
                 let generic_params = db.generic_params(id.parent);
@@ -1083,10 +1160,35 @@ impl<T: TypeWalk> TypeWalk for Vec<T> {
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum OpaqueTyId {
     ReturnTypeImplTrait(hir_def::FunctionId, u16),
+    /// `type Assoc = impl Trait;` in an impl.
+    AssocTyImplTrait(TypeAliasId),
+}
+
+impl OpaqueTyId {
+    /// The bounds of the opaque type. The outer binders are the generic
+    /// parameters of the item defining it, the inner one is the opaque type
+    /// itself.
+    pub(crate) fn bounds(
+        self,
+        db: &dyn HirDatabase,
+    ) -> Option<Binders<Binders<Vec<GenericPredicate>>>> {
+        match self {
+            OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
+                let datas = db.return_type_impl_traits(func)?;
+                Some((*datas).as_ref().map(|rpit| rpit.impl_traits[idx as usize].bounds.clone()))
+            }
+            OpaqueTyId::AssocTyImplTrait(type_alias) => {
+                let data = lower::assoc_ty_impl_trait(db, type_alias)?;
+                Some(data.map(|it| it.bounds))
+            }
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct ReturnTypeImplTraits {
+    /// Indexed by the `u16` in `OpaqueTyId::ReturnTypeImplTrait`, so looking
+    /// up the bounds of a particular `impl Trait` doesn't need a search.
     pub(crate) impl_traits: Vec<ReturnTypeImplTrait>,
 }
 
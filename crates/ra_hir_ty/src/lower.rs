@@ -5,22 +5,24 @@
 //!  - Building the type for an item: This happens through the `type_for_def` query.
 //!
 //! This usually involves resolving names, collecting generic arguments etc.
-use std::{iter, sync::Arc};
+use std::{fmt, iter, sync::Arc};
 
 use hir_def::{
     adt::StructKind,
+    body::LowerCtx,
     builtin_type::BuiltinType,
     generics::{TypeParamProvenance, WherePredicate, WherePredicateTarget},
     path::{GenericArg, Path, PathSegment, PathSegments},
     resolver::{HasResolver, Resolver, TypeNs},
     type_ref::{TypeBound, TypeRef},
     AdtId, AssocContainerId, AssocItemId, ConstId, EnumId, EnumVariantId, FunctionId, GenericDefId,
-    HasModule, ImplId, LocalFieldId, Lookup, StaticId, StructId, TraitId, TypeAliasId, TypeParamId,
-    UnionId, VariantId,
+    HasModule, ImplId, LocalFieldId, Lookup, ModuleId, StaticId, StructId, TraitId, TypeAliasId,
+    TypeParamId, UnionId, VariantId,
 };
-use hir_expand::name::Name;
+use hir_expand::{hygiene::Hygiene, name::Name};
 use ra_arena::map::ArenaMap;
 use ra_db::CrateId;
+use ra_syntax::{ast, SourceFile, SyntaxError, TextRange, TextSize};
 use smallvec::SmallVec;
 use stdx::impl_from;
 use test_utils::mark;
@@ -32,9 +34,9 @@ use crate::{
         all_super_trait_refs, associated_type_by_name_including_super_traits, generics,
         make_mut_slice, variant_data,
     },
-    Binders, BoundVar, DebruijnIndex, FnSig, GenericPredicate, OpaqueTy, OpaqueTyId, PolyFnSig,
-    ProjectionPredicate, ProjectionTy, ReturnTypeImplTrait, ReturnTypeImplTraits, Substs,
-    TraitEnvironment, TraitRef, Ty, TypeCtor, TypeWalk,
+    ApplicationTy, Binders, BoundVar, DebruijnIndex, FnSig, GenericPredicate, OpaqueTy, OpaqueTyId,
+    PolyFnSig, ProjectionPredicate, ProjectionTy, ReturnTypeImplTrait, ReturnTypeImplTraits,
+    Substs, TraitEnvironment, TraitRef, Ty, TypeCtor, TypeWalk,
 };
 
 #[derive(Debug)]
@@ -139,7 +141,63 @@ pub enum TypeParamLoweringMode {
     Variable,
 }
 
+/// The reason why `Ty::try_from_str` failed, i.e. the errors from parsing the
+/// string as a type. Their ranges are relative to the string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TyParseError {
+    pub errors: Vec<SyntaxError>,
+}
+
+impl TyParseError {
+    fn new(s: &str) -> TyParseError {
+        // `ast::TypeRef::parse` doesn't tell us what's wrong, so we parse the
+        // type as part of a type alias to get the errors.
+        const PREFIX: &str = "type T = ";
+        let parse = SourceFile::parse(&format!("{}{};", PREFIX, s));
+        let mut errors: Vec<_> = parse
+            .errors()
+            .iter()
+            .map(|err| {
+                let range = err.range().checked_sub(TextSize::of(PREFIX));
+                err.clone().with_range(range.unwrap_or_else(|| TextRange::empty(0.into())))
+            })
+            .collect();
+        if errors.is_empty() {
+            // e.g. `i32; struct S`, which is fine as part of a file
+            errors.push(SyntaxError::new(
+                "expected a single type",
+                TextRange::up_to(TextSize::of(s)),
+            ));
+        }
+        TyParseError { errors }
+    }
+}
+
+impl fmt::Display for TyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, err) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{:?}: {}", err.range(), err)?;
+        }
+        Ok(())
+    }
+}
+
 impl Ty {
+    /// Parses `s` as a type and lowers it in the root module of `krate`. This
+    /// is meant for tests and tooling; it fails only if `s` doesn't parse.
+    pub fn try_from_str(db: &dyn HirDatabase, krate: CrateId, s: &str) -> Result<Ty, TyParseError> {
+        let ast_type = ast::TypeRef::parse(s).map_err(|()| TyParseError::new(s))?;
+        let type_ref =
+            TypeRef::from_ast(&LowerCtx::with_hygiene(&Hygiene::new_unhygienic()), ast_type);
+        let def_map = db.crate_def_map(krate);
+        let resolver = ModuleId { krate, local_id: def_map.root }.resolver(db.upcast());
+        let ctx = TyLoweringContext::new(db, &resolver);
+        Ok(Ty::from_hir(&ctx, &type_ref))
+    }
+
     pub fn from_hir(ctx: &TyLoweringContext<'_>, type_ref: &TypeRef) -> Self {
         Ty::from_hir_ext(ctx, type_ref).0
     }
@@ -740,6 +798,27 @@ fn assoc_type_bindings_from_type_bound<'a>(
         })
 }
 
+/// Lowers the bounds of `type Assoc = impl Trait;` in an impl, if the type
+/// alias is of that form.
+pub(crate) fn assoc_ty_impl_trait(
+    db: &dyn HirDatabase,
+    def: TypeAliasId,
+) -> Option<Binders<ReturnTypeImplTrait>> {
+    if !matches!(def.lookup(db.upcast()).container, AssocContainerId::ImplId(_)) {
+        return None;
+    }
+    let data = db.type_alias_data(def);
+    let bounds = match data.type_ref.as_ref()? {
+        TypeRef::ImplTrait(bounds) => bounds,
+        _ => return None,
+    };
+    let resolver = def.resolver(db.upcast());
+    let ctx =
+        TyLoweringContext::new(db, &resolver).with_type_param_mode(TypeParamLoweringMode::Variable);
+    let generics = generics(db.upcast(), def.into());
+    Some(Binders::new(generics.len(), ReturnTypeImplTrait::from_hir(&ctx, bounds)))
+}
+
 impl ReturnTypeImplTrait {
     fn from_hir(ctx: &TyLoweringContext, bounds: &[TypeBound]) -> Self {
         mark::hit!(lower_rpit);
@@ -798,10 +877,8 @@ pub fn associated_type_shorthand_candidates<R>(
                 if generics.params.types[param_id.local_id].provenance
                     == TypeParamProvenance::TraitSelf
                 {
-                    let trait_ref = TraitRef {
-                        trait_: trait_id,
-                        substs: Substs::bound_vars(&generics, DebruijnIndex::INNERMOST),
-                    };
+                    let trait_ref =
+                        TraitRef { trait_: trait_id, substs: Substs::for_generics(&generics) };
                     traits_.push(trait_ref);
                 }
             }
@@ -899,6 +976,16 @@ impl TraitEnvironment {
             .collect::<Vec<_>>();
 
         if let Some(def) = resolver.generic_def() {
+            if let GenericDefId::FunctionId(f) = def {
+                // the parameter types of the function are assumed to be
+                // well-formed, which implies the where clauses of the ADTs in
+                // them, e.g. `T: Clone` for a parameter of type `S<T>` if
+                // `struct S<T: Clone>`
+                let sig = db.callable_item_signature(f.into()).subst(&Substs::type_params(db, f));
+                for ty in sig.params() {
+                    add_implied_bounds(db, ty, &mut predicates);
+                }
+            }
             let container: Option<AssocContainerId> = match def {
                 // FIXME: is there a function for this?
                 GenericDefId::FunctionId(f) => Some(f.lookup(db.upcast()).container),
@@ -926,6 +1013,21 @@ impl TraitEnvironment {
     }
 }
 
+/// Adds the where clauses of the ADTs in `ty`, which hold if `ty` is
+/// well-formed.
+fn add_implied_bounds(db: &dyn HirDatabase, ty: &Ty, predicates: &mut Vec<GenericPredicate>) {
+    ty.walk(&mut |ty| {
+        if let Ty::Apply(ApplicationTy { ctor: TypeCtor::Adt(adt), parameters }) = ty {
+            for pred in db.generic_predicates((*adt).into()).iter() {
+                let pred = pred.clone().subst(parameters);
+                if !pred.is_error() && !predicates.contains(&pred) {
+                    predicates.push(pred);
+                }
+            }
+        }
+    });
+}
+
 /// Resolve the where clause(s) of an item with generics.
 pub(crate) fn generic_predicates_query(
     db: &dyn HirDatabase,
@@ -1003,7 +1105,7 @@ fn fn_sig_for_fn(db: &dyn HirDatabase, def: FunctionId) -> PolyFnSig {
 /// function body.
 fn type_for_fn(db: &dyn HirDatabase, def: FunctionId) -> Binders<Ty> {
     let generics = generics(db.upcast(), def.into());
-    let substs = Substs::bound_vars(&generics, DebruijnIndex::INNERMOST);
+    let substs = Substs::for_generics(&generics);
     Binders::new(substs.len(), Ty::apply(TypeCtor::FnDef(def.into()), substs))
 }
 
@@ -1057,7 +1159,7 @@ fn type_for_struct_constructor(db: &dyn HirDatabase, def: StructId) -> Binders<T
         return type_for_adt(db, def.into());
     }
     let generics = generics(db.upcast(), def.into());
-    let substs = Substs::bound_vars(&generics, DebruijnIndex::INNERMOST);
+    let substs = Substs::for_generics(&generics);
     Binders::new(substs.len(), Ty::apply(TypeCtor::FnDef(def.into()), substs))
 }
 
@@ -1082,13 +1184,13 @@ fn type_for_enum_variant_constructor(db: &dyn HirDatabase, def: EnumVariantId) -
         return type_for_adt(db, def.parent.into());
     }
     let generics = generics(db.upcast(), def.parent.into());
-    let substs = Substs::bound_vars(&generics, DebruijnIndex::INNERMOST);
+    let substs = Substs::for_generics(&generics);
     Binders::new(substs.len(), Ty::apply(TypeCtor::FnDef(def.into()), substs))
 }
 
 fn type_for_adt(db: &dyn HirDatabase, adt: AdtId) -> Binders<Ty> {
     let generics = generics(db.upcast(), adt.into());
-    let substs = Substs::bound_vars(&generics, DebruijnIndex::INNERMOST);
+    let substs = Substs::for_generics(&generics);
     Binders::new(substs.len(), Ty::apply(TypeCtor::Adt(adt), substs))
 }
 
@@ -1098,11 +1200,16 @@ fn type_for_type_alias(db: &dyn HirDatabase, t: TypeAliasId) -> Binders<Ty> {
     let ctx =
         TyLoweringContext::new(db, &resolver).with_type_param_mode(TypeParamLoweringMode::Variable);
     let type_ref = &db.type_alias_data(t).type_ref;
-    let substs = Substs::bound_vars(&generics, DebruijnIndex::INNERMOST);
+    let substs = Substs::for_generics(&generics);
     let inner = Ty::from_hir(&ctx, type_ref.as_ref().unwrap_or(&TypeRef::Error));
     Binders::new(substs.len(), inner)
 }
 
+/// A definition that can be called and has its own `FnDef` type.
+///
+/// Function pointers are deliberately not included: they don't have a
+/// definition, and Chalk represents them as `TyData::Function` types rather
+/// than `FnDef`s, so there is no `fn_def_datum` to provide for them.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CallableDef {
     FunctionId(FunctionId),
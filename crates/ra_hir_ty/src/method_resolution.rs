@@ -21,7 +21,7 @@ use crate::{
     autoderef,
     db::HirDatabase,
     primitive::{FloatBitness, FloatTy, IntTy},
-    utils::all_super_traits,
+    utils::{all_super_trait_refs, all_super_traits},
     ApplicationTy, Canonical, DebruijnIndex, InEnvironment, TraitEnvironment, TraitRef, Ty, TyKind,
     TypeCtor, TypeWalk,
 };
@@ -146,9 +146,34 @@ impl TraitImpls {
         Arc::new(res)
     }
 
+    /// Like `trait_impls_in_deps_query`, but only collects the impls of
+    /// `trait_`. Since the result only changes if impls of that trait change,
+    /// queries using it don't need to be recomputed for unrelated impls.
+    pub(crate) fn trait_impls_in_deps_for_query(
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        trait_: TraitId,
+    ) -> Arc<Self> {
+        let _p = profile("trait_impls_in_deps_for_query");
+        let crate_graph = db.crate_graph();
+        let mut res = Self { map: FxHashMap::default() };
+
+        for krate in crate_graph.transitive_deps(krate) {
+            res.merge_trait(&db.trait_impls_in_crate(krate), trait_);
+        }
+
+        Arc::new(res)
+    }
+
     fn merge(&mut self, other: &Self) {
-        for (trait_, other_map) in &other.map {
-            let map = self.map.entry(*trait_).or_default();
+        for &trait_ in other.map.keys() {
+            self.merge_trait(other, trait_);
+        }
+    }
+
+    fn merge_trait(&mut self, other: &Self, trait_: TraitId) {
+        if let Some(other_map) = other.map.get(&trait_) {
+            let map = self.map.entry(trait_).or_default();
             for (fp, impls) in other_map {
                 let vec = map.entry(*fp).or_default();
                 vec.extend(impls);
@@ -228,6 +253,18 @@ impl InherentImpls {
     }
 }
 
+/// All impls written in the source code of the crate, both inherent and trait
+/// impls. Unlike `TraitImpls` and `InherentImpls`, this also contains impls we
+/// couldn't resolve the trait or self type of, but none of the builtin impls
+/// (e.g. of the `Fn` traits for closures) that only exist for Chalk.
+pub(crate) fn all_explicit_impls_in_krate_query(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+) -> Arc<[ImplId]> {
+    let crate_def_map = db.crate_def_map(krate);
+    crate_def_map.modules.iter().flat_map(|(_, module_data)| module_data.scope.impls()).collect()
+}
+
 impl Ty {
     pub fn def_crates(
         &self,
@@ -518,6 +555,16 @@ fn iterate_method_candidates_for_self_ty(
     iterate_trait_method_candidates(self_ty, db, env, krate, traits_in_scope, name, None, callback)
 }
 
+/// Given that `trait_ref` holds, returns the trait refs this implies through
+/// the super trait hierarchy, including `trait_ref` itself. The parameters of
+/// the super traits are substituted accordingly.
+pub(crate) fn compute_super_trait_impls(
+    db: &dyn HirDatabase,
+    trait_ref: TraitRef,
+) -> Vec<TraitRef> {
+    all_super_trait_refs(db, trait_ref)
+}
+
 fn iterate_trait_method_candidates(
     self_ty: &Canonical<Ty>,
     db: &dyn HirDatabase,
@@ -531,24 +578,24 @@ fn iterate_trait_method_candidates(
     // if ty is `dyn Trait`, the trait doesn't need to be in scope
     let inherent_trait =
         self_ty.value.dyn_trait().into_iter().flat_map(|t| all_super_traits(db.upcast(), t));
-    let env_traits = if let Ty::Placeholder(_) = self_ty.value {
-        // if we have `T: Trait` in the param env, the trait doesn't need to be in scope
+    let env_trait_refs: Vec<TraitRef> = if let Ty::Placeholder(_) = self_ty.value {
+        // if we have `T: Trait` in the param env, the trait doesn't need to be in
+        // scope, and we know that `T` implements it and its super traits
         env.trait_predicates_for_self_ty(&self_ty.value)
-            .map(|tr| tr.trait_)
-            .flat_map(|t| all_super_traits(db.upcast(), t))
+            .flat_map(|tr| compute_super_trait_impls(db, tr.clone()))
             .collect()
     } else {
         Vec::new()
     };
-    let traits =
-        inherent_trait.chain(env_traits.into_iter()).chain(traits_in_scope.iter().copied());
+    let env_traits = env_trait_refs.iter().map(|tr| tr.trait_);
+    let traits = inherent_trait.chain(env_traits).chain(traits_in_scope.iter().copied());
     'traits: for t in traits {
         let data = db.trait_data(t);
 
         // we'll be lazy about checking whether the type implements the
         // trait, but if we find out it doesn't, we'll skip the rest of the
         // iteration
-        let mut known_implemented = false;
+        let mut known_implemented = env_trait_refs.iter().any(|tr| tr.trait_ == t);
         for (_name, item) in data.items.iter() {
             if !is_valid_candidate(db, name, receiver_ty, *item, self_ty) {
                 continue;
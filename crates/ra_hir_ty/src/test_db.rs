@@ -5,18 +5,39 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use hir_def::{db::DefDatabase, AssocItemId, ModuleDefId, ModuleId};
+use hir_def::{
+    db::DefDatabase, AdtId, AssocItemId, DefWithBodyId, EnumId, FunctionId, ImplId, ModuleDefId,
+    ModuleId, StructId, TraitId,
+};
 use hir_expand::{
     db::AstDatabase,
     diagnostics::{Diagnostic, DiagnosticSink},
+    InFile,
+};
+use ra_db::{
+    fixture::WithFixture, salsa, CrateId, FileId, FileLoader, FileLoaderDelegate, SourceDatabase,
+    Upcast,
+};
+use ra_syntax::{
+    algo,
+    ast::{self, AstNode},
+    TextRange, TextSize,
 };
-use ra_db::{salsa, CrateId, FileId, FileLoader, FileLoaderDelegate, SourceDatabase, Upcast};
-use ra_syntax::TextRange;
 use rustc_hash::{FxHashMap, FxHashSet};
-use stdx::format_to;
-use test_utils::extract_annotations;
+use stdx::{format_to, trim_indent};
+use test_utils::{assert_eq_text, extract_annotations};
 
-use crate::diagnostics::validate_body;
+use crate::{
+    db::HirDatabase,
+    diagnostics::validate_body,
+    display::HirDisplay,
+    method_resolution::inherent_impl_substs,
+    traits::{
+        chalk::{self, Interner, ToChalk, TraitQueryMetrics},
+        Impl, SolveStats,
+    },
+    Canonical, Ty,
+};
 
 #[salsa::database(
     ra_db::SourceDatabaseExtStorage,
@@ -26,11 +47,18 @@ use crate::diagnostics::validate_body;
     hir_def::db::DefDatabaseStorage,
     crate::db::HirDatabaseStorage
 )]
-#[derive(Default)]
 pub struct TestDB {
     storage: salsa::Storage<TestDB>,
     events: Mutex<Option<Vec<salsa::Event>>>,
 }
+impl Default for TestDB {
+    fn default() -> Self {
+        let mut this = TestDB { storage: Default::default(), events: Default::default() };
+        this.set_chalk_solver_config(Default::default());
+        this
+    }
+}
+
 impl fmt::Debug for TestDB {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TestDB").finish()
@@ -82,6 +110,15 @@ impl FileLoader for TestDB {
 }
 
 impl TestDB {
+    /// Finds the crate called `name` in the crate graph.
+    pub fn crate_named(&self, name: &str) -> CrateId {
+        let crate_graph = self.crate_graph();
+        crate_graph
+            .iter()
+            .find(|&krate| crate_graph[krate].display_name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("no crate named `{}`", name))
+    }
+
     pub fn module_for_file(&self, file_id: FileId) -> ModuleId {
         for &krate in self.relevant_crates(file_id).iter() {
             let crate_def_map = self.crate_def_map(krate);
@@ -94,51 +131,116 @@ impl TestDB {
         panic!("Can't find module for file")
     }
 
-    fn diag<F: FnMut(&dyn Diagnostic)>(&self, mut cb: F) {
+    /// Resolves a module path like `foo::bar`, relative to the module of
+    /// `file_id`.
+    pub fn module_for_path(&self, file_id: FileId, path: &str) -> ModuleId {
+        let mut module = self.module_for_file(file_id);
+        let crate_def_map = self.crate_def_map(module.krate);
+        for segment in path.split("::") {
+            let children = &crate_def_map[module.local_id].children;
+            module.local_id = children
+                .iter()
+                .find_map(
+                    |(name, &child)| if name.to_string() == segment { Some(child) } else { None },
+                )
+                .unwrap_or_else(|| panic!("can't find module `{}` in `{}`", segment, path));
+        }
+        module
+    }
+
+    /// All bodies of all crates that we check, i.e. those of free and impl
+    /// items.
+    fn bodies(&self) -> Vec<DefWithBodyId> {
         let crate_graph = self.crate_graph();
+        let mut bodies = Vec::new();
         for krate in crate_graph.iter() {
             let crate_def_map = self.crate_def_map(krate);
+            for (local_id, _) in crate_def_map.modules.iter() {
+                bodies.extend(self.module_bodies(ModuleId { krate, local_id }));
+            }
+        }
+        bodies
+    }
 
-            let mut fns = Vec::new();
-            for (module_id, _) in crate_def_map.modules.iter() {
-                for decl in crate_def_map[module_id].scope.declarations() {
-                    if let ModuleDefId::FunctionId(f) = decl {
-                        fns.push(f)
-                    }
-                }
+    /// The bodies of the items declared directly in `module`, including
+    /// those of items in its impls.
+    fn module_bodies(&self, module: ModuleId) -> Vec<DefWithBodyId> {
+        let mut bodies: Vec<DefWithBodyId> = Vec::new();
+        let crate_def_map = self.crate_def_map(module.krate);
+        let scope = &crate_def_map[module.local_id].scope;
+        for decl in scope.declarations() {
+            match decl {
+                ModuleDefId::FunctionId(it) => bodies.push(it.into()),
+                ModuleDefId::ConstId(it) => bodies.push(it.into()),
+                ModuleDefId::StaticId(it) => bodies.push(it.into()),
+                _ => {}
+            }
+        }
 
-                for impl_id in crate_def_map[module_id].scope.impls() {
-                    let impl_data = self.impl_data(impl_id);
-                    for item in impl_data.items.iter() {
-                        if let AssocItemId::FunctionId(f) = item {
-                            fns.push(*f)
-                        }
-                    }
+        for impl_id in scope.impls() {
+            let impl_data = self.impl_data(impl_id);
+            for item in impl_data.items.iter() {
+                match *item {
+                    AssocItemId::FunctionId(it) => bodies.push(it.into()),
+                    AssocItemId::ConstId(it) => bodies.push(it.into()),
+                    AssocItemId::TypeAliasId(_) => {}
                 }
             }
+        }
+        bodies
+    }
 
-            for f in fns {
-                let mut sink = DiagnosticSink::new(&mut cb);
-                validate_body(self, f.into(), &mut sink);
-            }
+    fn diag<F: FnMut(&dyn Diagnostic)>(&self, bodies: Vec<DefWithBodyId>, mut cb: F) {
+        for body in bodies {
+            let mut sink = DiagnosticSink::new(&mut cb);
+            validate_body(self, body, &mut sink);
         }
     }
 
     pub fn diagnostics(&self) -> (String, u32) {
+        self.format_diagnostics(self.bodies())
+    }
+
+    /// Like `diagnostics`, but only for the items declared directly in
+    /// `module`.
+    pub fn module_diagnostics(&self, module: ModuleId) -> (String, u32) {
+        self.format_diagnostics(self.module_bodies(module))
+    }
+
+    fn format_diagnostics(&self, bodies: Vec<DefWithBodyId>) -> (String, u32) {
         let mut buf = String::new();
         let mut count = 0;
-        self.diag(|d| {
+        self.diag(bodies, |d| {
             format_to!(buf, "{:?}: {}\n", d.syntax_node(self).text(), d.message());
             count += 1;
         });
         (buf, count)
     }
 
+    /// Panics, showing the diagnostics, if there are any.
+    pub fn assert_no_diagnostics(&self) {
+        let (diagnostics, count) = self.diagnostics();
+        assert!(count == 0, "expected no diagnostics, got {}:\n{}", count, diagnostics);
+    }
+
+    /// Panics, showing the diagnostics, if there aren't exactly `expected`
+    /// of them.
+    pub fn assert_diagnostic_count(&self, expected: u32) {
+        let (diagnostics, count) = self.diagnostics();
+        assert!(
+            count == expected,
+            "expected {} diagnostics, got {}:\n{}",
+            expected,
+            count,
+            diagnostics
+        );
+    }
+
     /// Like `diagnostics`, but filtered for a single diagnostic.
     pub fn diagnostic<D: Diagnostic>(&self) -> (String, u32) {
         let mut buf = String::new();
         let mut count = 0;
-        self.diag(|d| {
+        self.diag(self.bodies(), |d| {
             // We want to filter diagnostics by the particular one we are testing for, to
             // avoid surprising results in tests.
             if d.downcast_ref::<D>().is_some() {
@@ -149,6 +251,145 @@ impl TestDB {
         (buf, count)
     }
 
+    /// Statistics about the `impls_for_trait` lookups Chalk does during `f`.
+    pub fn log_trait_query_metrics(&self, f: impl FnOnce()) -> TraitQueryMetrics {
+        crate::traits::chalk::log_trait_query_metrics(f)
+    }
+
+    /// Solves a closed Chalk goal in an empty environment, bypassing type
+    /// inference and our own goal representation.
+    pub fn run_chalk_query(
+        &self,
+        krate: CrateId,
+        goal: chalk_ir::Goal<Interner>,
+    ) -> Option<chalk_solve::Solution<Interner>> {
+        let in_env = chalk_ir::InEnvironment::new(&chalk_ir::Environment::new(&Interner), goal);
+        let canonical = chalk_ir::Canonical {
+            value: in_env,
+            binders: chalk_ir::CanonicalVarKinds::empty(&Interner),
+        };
+        crate::traits::solve(self, krate, &chalk_ir::UCanonical { canonical, universes: 1 })
+    }
+
+    /// Asserts that exactly `expected` impls of the trait called `trait_name`
+    /// are visible from `krate`.
+    pub fn check_impl_count(&self, krate: CrateId, trait_name: &str, expected: usize) {
+        let count = self.visible_impl_count(krate, trait_name);
+        assert_eq!(count, expected, "wrong number of `{}` impls", trait_name);
+    }
+
+    /// Like `check_impl_count`, but only checks that there are at least
+    /// `expected` impls.
+    pub fn check_impl_count_gte(&self, krate: CrateId, trait_name: &str, expected: usize) {
+        let count = self.visible_impl_count(krate, trait_name);
+        assert!(
+            count >= expected,
+            "expected at least {} `{}` impls, found {}",
+            expected,
+            trait_name,
+            count
+        );
+    }
+
+    /// Finds the impl of the trait called `trait_name` for the type
+    /// `self_ty_str` (written like in the source code) that is visible from
+    /// `krate`, if there is one.
+    pub fn impl_trait_for(
+        &self,
+        krate: CrateId,
+        self_ty_str: &str,
+        trait_name: &str,
+    ) -> Option<ImplId> {
+        let self_ty = Ty::try_from_str(self, krate, self_ty_str)
+            .unwrap_or_else(|err| panic!("can't parse type `{}`: {}", self_ty_str, err));
+        let self_ty = Canonical::new(self_ty, std::iter::empty());
+        let trait_ = self.visible_trait(krate, trait_name);
+        let in_crate = self.trait_impls_in_crate(krate);
+        let in_deps = self.trait_impls_in_deps(krate);
+        let mut impls = in_crate.for_trait(trait_).chain(in_deps.for_trait(trait_));
+        impls.find(|&impl_id| inherent_impl_substs(self, impl_id, &self_ty).is_some())
+    }
+
+    /// The Chalk ids of the trait impl blocks visible from `krate`, i.e. those
+    /// in `krate` and its dependencies.
+    pub fn all_chalk_impls(&self, krate: CrateId) -> Vec<chalk::ImplId> {
+        let crate_graph = self.crate_graph();
+        std::iter::once(krate)
+            .chain(crate_graph.transitive_deps(krate))
+            .flat_map(|krate| {
+                let impls = self.trait_impls_in_crate(krate);
+                impls.all_impls().map(|it| Impl::ImplDef(it).to_chalk(self)).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Pairs of different impls visible from `krate` that lower to the same
+    /// Chalk datum. That most likely means that two different `Impl`s got
+    /// mapped to the same Chalk id, or that the lowering lost some
+    /// information; duplicate impls in user code can cause it as well.
+    pub fn duplicate_impl_datums(&self, krate: CrateId) -> Vec<(chalk::ImplId, chalk::ImplId)> {
+        let datums: Vec<_> = self
+            .all_chalk_impls(krate)
+            .into_iter()
+            .map(|impl_id| (impl_id, self.impl_datum(krate, impl_id)))
+            .collect();
+        let mut duplicates = Vec::new();
+        for (i, (impl_id, datum)) in datums.iter().enumerate() {
+            for (other_id, other) in &datums[..i] {
+                if datum.polarity == other.polarity && datum.binders == other.binders {
+                    duplicates.push((*other_id, *impl_id));
+                }
+            }
+        }
+        duplicates
+    }
+
+    /// Finds the item called `name` that is declared in or imported into
+    /// `module`, e.g. `let trait_: TraitId = db.item_named(module, "Trait");`.
+    pub fn item_named<T: ModuleItem>(&self, module: ModuleId, name: &str) -> T {
+        let crate_def_map = self.crate_def_map(module.krate);
+        let item = crate_def_map[module.local_id]
+            .scope
+            .entries()
+            .filter(|(item_name, _)| item_name.to_string() == name)
+            .flat_map(|(_, def)| def.take_types().into_iter().chain(def.take_values()))
+            .find_map(T::from_module_def);
+        item.unwrap_or_else(|| panic!("can't find `{}`", name))
+    }
+
+    /// All items of kind `T` declared directly in `module`.
+    pub fn module_items<T: ModuleItem>(&self, module: ModuleId) -> Vec<T> {
+        let crate_def_map = self.crate_def_map(module.krate);
+        let items = crate_def_map[module.local_id].scope.declarations();
+        items.filter_map(T::from_module_def).collect()
+    }
+
+    fn visible_impl_count(&self, krate: CrateId, trait_name: &str) -> usize {
+        let trait_ = self.visible_trait(krate, trait_name);
+        let in_crate = self.trait_impls_in_crate(krate).for_trait(trait_).count();
+        let in_deps = self.trait_impls_in_deps(krate).for_trait(trait_).count();
+        in_crate + in_deps
+    }
+
+    fn visible_trait(&self, krate: CrateId, trait_name: &str) -> TraitId {
+        let crate_graph = self.crate_graph();
+        std::iter::once(krate)
+            .chain(crate_graph.transitive_deps(krate))
+            .find_map(|krate| self.find_trait(krate, trait_name))
+            .unwrap_or_else(|| panic!("can't find trait `{}`", trait_name))
+    }
+
+    fn find_trait(&self, krate: CrateId, trait_name: &str) -> Option<TraitId> {
+        let crate_def_map = self.crate_def_map(krate);
+        let mut traits = crate_def_map.modules.iter().flat_map(|(_, module_data)| {
+            module_data.scope.declarations().filter_map(|decl| match decl {
+                ModuleDefId::TraitId(it) => Some(it),
+                _ => None,
+            })
+        });
+        traits.find(|&it| self.trait_data(it).name.to_string() == trait_name)
+    }
+
     pub fn extract_annotations(&self) -> FxHashMap<FileId, Vec<(TextRange, String)>> {
         let mut files = Vec::new();
         let crate_graph = self.crate_graph();
@@ -171,6 +412,16 @@ impl TestDB {
             })
             .collect()
     }
+
+    /// Returns the type expected by the `//^ Type` (or `//^ type: Type`)
+    /// annotation pointing at `offset`, if there is one.
+    pub fn extract_type_at(&self, file_id: FileId, offset: TextSize) -> Option<String> {
+        let annotations = self.extract_annotations().remove(&file_id)?;
+        annotations
+            .into_iter()
+            .find(|(range, _)| range.contains_inclusive(offset))
+            .map(|(_, annotation)| expected_type(&annotation).to_string())
+    }
 }
 
 impl TestDB {
@@ -182,6 +433,22 @@ impl TestDB {
 
     pub fn log_executed(&self, f: impl FnOnce()) -> Vec<String> {
         let events = self.log(f);
+        self.executed_queries(events)
+    }
+
+    /// Like `log_executed`, but for the work Chalk does on the goals it is
+    /// asked to solve.
+    pub fn log_chalk_stats(&self, f: impl FnOnce()) -> Vec<SolveStats> {
+        crate::traits::log_solve_stats(f)
+    }
+
+    /// Like `log_executed`, but shows the goals Chalk is asked to solve,
+    /// printed with the names of the traits and types involved.
+    pub fn log_chalk_queries(&self, f: impl FnOnce()) -> Vec<String> {
+        crate::traits::log_chalk_goals(f)
+    }
+
+    fn executed_queries(&self, events: Vec<salsa::Event>) -> Vec<String> {
         events
             .into_iter()
             .filter_map(|e| match e.kind {
@@ -194,4 +461,157 @@ impl TestDB {
             })
             .collect()
     }
+
+    /// Checks the inferred types of all expressions annotated with `//^ Type`
+    /// against their annotations, and panics with a diff listing all
+    /// mismatches.
+    pub fn check_infer(&self) {
+        let mut annotations: Vec<_> = self.extract_annotations().into_iter().collect();
+        assert!(!annotations.is_empty(), "no `//^` annotations found");
+        annotations.sort_by_key(|(file_id, _)| file_id.0);
+
+        let bodies: Vec<_> = self
+            .bodies()
+            .into_iter()
+            .map(|def| (self.body_with_source_map(def).1, self.infer(def)))
+            .collect();
+        let mut expected = String::new();
+        let mut actual = String::new();
+        for (file_id, annotations) in annotations {
+            let file = self.parse(file_id).tree();
+            for (range, annotation) in annotations {
+                let ty =
+                    algo::find_node_at_range::<ast::Expr>(file.syntax(), range).and_then(|expr| {
+                        let src = InFile::new(file_id.into(), &expr);
+                        bodies.iter().find_map(|(source_map, infer)| {
+                            source_map.node_expr(src).map(|expr_id| infer[expr_id].clone())
+                        })
+                    });
+                let ty = match ty {
+                    Some(ty) => ty.display(self).to_string(),
+                    None => "{no expression}".to_string(),
+                };
+                format_to!(expected, "{:?}: {}\n", range, expected_type(&annotation));
+                format_to!(actual, "{:?}: {}\n", range, ty);
+            }
+        }
+        assert_eq_text!(&expected, &actual);
+    }
+
+    /// Starts recording query executions; pass the result to
+    /// `assert_salsa_state_unchanged` to check that nothing was recomputed in
+    /// the meantime.
+    pub fn snapshot_salsa_state(&self) -> SalsaStateSnapshot {
+        *self.events.lock().unwrap() = Some(Vec::new());
+        SalsaStateSnapshot { _private: () }
+    }
+
+    pub fn assert_salsa_state_unchanged(&self, _snapshot: SalsaStateSnapshot) {
+        let events = self.events.lock().unwrap().take().expect("no salsa state snapshot taken");
+        let executed = self.executed_queries(events);
+        assert!(executed.is_empty(), "queries were re-executed: {:#?}", executed);
+    }
+}
+
+/// Marks the point at which `TestDB::snapshot_salsa_state` started recording
+/// query executions.
+#[must_use]
+pub struct SalsaStateSnapshot {
+    _private: (),
+}
+
+/// Builds a `TestDB` containing several crates, one file each. Every crate
+/// depends on all crates added before it, so e.g. a crate added first can play
+/// the role of `std`.
+#[derive(Default)]
+pub struct TestDBBuilder {
+    crates: Vec<(String, String)>,
+}
+
+impl TestDBBuilder {
+    pub fn new() -> TestDBBuilder {
+        TestDBBuilder::default()
+    }
+
+    pub fn with_crate(mut self, name: &str, text: &str) -> TestDBBuilder {
+        self.crates.push((name.to_string(), trim_indent(text)));
+        self
+    }
+
+    /// Use `TestDB::crate_named` to find the crates afterwards.
+    pub fn build(self) -> TestDB {
+        let mut fixture = String::new();
+        let mut deps: Vec<&str> = Vec::new();
+        for (name, text) in &self.crates {
+            format_to!(fixture, "//- /{}/lib.rs crate:{}", name, name);
+            if !deps.is_empty() {
+                format_to!(fixture, " deps:{}", deps.join(","));
+            }
+            format_to!(fixture, "\n{}\n", text);
+            deps.push(name);
+        }
+        TestDB::with_files(&fixture)
+    }
+}
+
+/// The kinds of items tests can look up with `TestDB::item_named` and
+/// `TestDB::module_items`.
+pub trait ModuleItem: Sized {
+    fn from_module_def(def: ModuleDefId) -> Option<Self>;
+}
+
+impl ModuleItem for ModuleDefId {
+    fn from_module_def(def: ModuleDefId) -> Option<Self> {
+        Some(def)
+    }
+}
+
+impl ModuleItem for FunctionId {
+    fn from_module_def(def: ModuleDefId) -> Option<Self> {
+        match def {
+            ModuleDefId::FunctionId(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl ModuleItem for AdtId {
+    fn from_module_def(def: ModuleDefId) -> Option<Self> {
+        match def {
+            ModuleDefId::AdtId(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl ModuleItem for StructId {
+    fn from_module_def(def: ModuleDefId) -> Option<Self> {
+        match def {
+            ModuleDefId::AdtId(AdtId::StructId(it)) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl ModuleItem for EnumId {
+    fn from_module_def(def: ModuleDefId) -> Option<Self> {
+        match def {
+            ModuleDefId::AdtId(AdtId::EnumId(it)) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+impl ModuleItem for TraitId {
+    fn from_module_def(def: ModuleDefId) -> Option<Self> {
+        match def {
+            ModuleDefId::TraitId(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+/// Type annotations can optionally be written as `//^ type: Type`.
+pub(crate) fn expected_type(annotation: &str) -> &str {
+    annotation.trim_start_matches("type: ")
 }
@@ -7,6 +7,8 @@ mod traits;
 mod method_resolution;
 mod macros;
 mod display_source_code;
+mod chalk;
+mod chalk_mapping;
 
 use std::sync::Arc;
 
@@ -21,16 +23,21 @@ use hir_def::{
 };
 use hir_expand::{db::AstDatabase, InFile};
 use insta::assert_snapshot;
-use ra_db::{fixture::WithFixture, FileRange, SourceDatabase, SourceDatabaseExt};
+use ra_db::{fixture::WithFixture, FileId, FileRange, SourceDatabase, SourceDatabaseExt};
 use ra_syntax::{
     algo,
     ast::{self, AstNode},
-    SyntaxNode,
+    SyntaxNode, TextSize,
 };
 use stdx::format_to;
 
 use crate::{
-    db::HirDatabase, display::HirDisplay, infer::TypeMismatch, test_db::TestDB, InferenceResult, Ty,
+    db::HirDatabase,
+    display::HirDisplay,
+    infer::TypeMismatch,
+    test_db::{expected_type, TestDB, TestDBBuilder},
+    traits::{ChalkSolverConfig, Solution},
+    InferenceResult, Ty, TypeCtor,
 };
 
 // These tests compare the inference results for all expressions in a file
@@ -47,27 +54,20 @@ fn setup_tracing() -> tracing::subscriber::DefaultGuard {
 }
 
 fn check_types(ra_fixture: &str) {
-    check_types_impl(ra_fixture, false)
+    let _tracing = setup_tracing();
+    TestDB::with_files(ra_fixture).check_infer();
 }
 
 fn check_types_source_code(ra_fixture: &str) {
-    check_types_impl(ra_fixture, true)
-}
-
-fn check_types_impl(ra_fixture: &str, display_source: bool) {
     let _tracing = setup_tracing();
     let db = TestDB::with_files(ra_fixture);
     let mut checked_one = false;
     for (file_id, annotations) in db.extract_annotations() {
         for (range, expected) in annotations {
             let ty = type_at_range(&db, FileRange { file_id, range });
-            let actual = if display_source {
-                let module = db.module_for_file(file_id);
-                ty.display_source_code(&db, module).unwrap()
-            } else {
-                ty.display(&db).to_string()
-            };
-            assert_eq!(expected, actual);
+            let module = db.module_for_file(file_id);
+            let actual = ty.display_source_code(&db, module).unwrap();
+            assert_eq!(expected_type(&expected), actual);
             checked_one = true;
         }
     }
@@ -209,6 +209,15 @@ fn infer_with_mismatches(content: &str, include_mismatches: bool) -> String {
     buf
 }
 
+/// Infers the types of all bodies in the module of `file_id`.
+fn infer_module(db: &TestDB, file_id: FileId) {
+    let module = db.module_for_file(file_id);
+    let crate_def_map = db.crate_def_map(module.krate);
+    visit_module(db, &crate_def_map, module.local_id, &mut |def| {
+        db.infer(def);
+    });
+}
+
 fn visit_module(
     db: &TestDB,
     crate_def_map: &CrateDefMap,
@@ -309,13 +318,7 @@ fn typing_whitespace_inside_a_function_should_not_invalidate_types() {
     ",
     );
     {
-        let events = db.log_executed(|| {
-            let module = db.module_for_file(pos.file_id);
-            let crate_def_map = db.crate_def_map(module.krate);
-            visit_module(&db, &crate_def_map, module.local_id, &mut |def| {
-                db.infer(def);
-            });
-        });
+        let events = db.log_executed(|| infer_module(&db, pos.file_id));
         assert!(format!("{:?}", events).contains("infer"))
     }
 
@@ -331,17 +334,539 @@ fn typing_whitespace_inside_a_function_should_not_invalidate_types() {
     db.set_file_text(pos.file_id, Arc::new(new_text));
 
     {
-        let events = db.log_executed(|| {
-            let module = db.module_for_file(pos.file_id);
-            let crate_def_map = db.crate_def_map(module.krate);
-            visit_module(&db, &crate_def_map, module.local_id, &mut |def| {
-                db.infer(def);
-            });
-        });
+        let events = db.log_executed(|| infer_module(&db, pos.file_id));
         assert!(!format!("{:?}", events).contains("infer"), "{:#?}", events)
     }
 }
 
+#[test]
+fn impls_for_trait_only_collects_impls_of_that_trait() {
+    let (db, files) = TestDB::with_files_by_path(
+        r#"
+//- /main.rs crate:main deps:dep
+use dep::Iterator;
+struct S;
+impl Iterator for S { type Item = u32; }
+fn test(s: S) {
+    s.next();
+}
+//- /lib.rs crate:dep
+pub trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item> {}
+}
+pub trait Other {}
+impl Other for u32 {}
+"#,
+    );
+    let events = db.log_executed(|| infer_module(&db, files["/main.rs"]));
+    assert!(events.iter().any(|it| it.starts_with("trait_impls_in_deps_for(")), "{:#?}", events);
+    assert!(!events.iter().any(|it| it.starts_with("trait_impls_in_deps(")), "{:#?}", events);
+}
+
+#[test]
+fn recomputing_types_without_changes_should_not_execute_queries() {
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        fn foo() -> i32 {
+            1 + 1
+        }
+    ",
+    );
+    let infer_all = || infer_module(&db, file_id);
+    infer_all();
+
+    let snapshot = db.snapshot_salsa_state();
+    infer_all();
+    db.assert_salsa_state_unchanged(snapshot);
+}
+
+#[test]
+fn impls_for_trait_records_metrics() {
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        trait Trait { fn foo(&self) -> u32; }
+        struct S;
+        impl Trait for S {}
+        fn test() {
+            S.foo();
+        }
+    ",
+    );
+    let metrics = db.log_trait_query_metrics(|| infer_module(&db, file_id));
+    assert_eq!(metrics.per_trait.len(), 1, "{:?}", metrics);
+    let trait_metrics = metrics.per_trait.values().next().unwrap();
+    assert!(trait_metrics.calls > 0);
+    assert_eq!(trait_metrics.unrestricted_calls, 0);
+    assert!(trait_metrics.impls_returned > 0);
+}
+
+#[test]
+fn int_vars_use_restricted_impl_search() {
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        trait Trait { fn foo(&self) -> u32; }
+        impl Trait for u32 {}
+        impl Trait for i64 {}
+        impl Trait for f32 {}
+        fn test() {
+            1.foo();
+            1.0.foo();
+        }
+    ",
+    );
+    let metrics = db.log_trait_query_metrics(|| infer_module(&db, file_id));
+    for trait_metrics in metrics.per_trait.values() {
+        assert_eq!(trait_metrics.unrestricted_calls, 0, "{:?}", metrics);
+    }
+}
+
+#[test]
+fn fn_ptrs_use_restricted_impl_search() {
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        trait Trait { fn foo(&self) -> u32; }
+        impl Trait for fn(i32) -> bool {}
+        impl Trait for fn(i32, i32) -> bool {}
+        impl Trait for u32 {}
+        fn test(f: fn(i32) -> bool) {
+            f.foo();
+        }
+    ",
+    );
+    let metrics = db.log_trait_query_metrics(|| infer_module(&db, file_id));
+    assert!(!metrics.per_trait.is_empty());
+    for trait_metrics in metrics.per_trait.values() {
+        assert_eq!(trait_metrics.unrestricted_calls, 0, "{:?}", metrics);
+    }
+}
+
+#[test]
+fn closures_use_restricted_impl_search() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+        #[lang = "fn_once"]
+        trait FnOnce<Args> { type Output; }
+        trait Trait { fn foo(&self) -> u32; }
+        impl Trait for u32 {}
+        impl Trait for fn(u32) -> u32 {}
+        fn test() {
+            let f = |x: u32| x;
+            f(1);
+            f.foo();
+        }
+    "#,
+    );
+    let metrics = db.log_trait_query_metrics(|| infer_module(&db, file_id));
+    assert!(!metrics.per_trait.is_empty());
+    for trait_metrics in metrics.per_trait.values() {
+        assert_eq!(trait_metrics.unrestricted_calls, 0, "{:?}", metrics);
+    }
+}
+
+#[test]
+fn chalk_solve_stats() {
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        trait Trait { fn foo(&self) -> u32; }
+        struct S;
+        impl Trait for S {}
+        fn test() {
+            S.foo();
+        }
+    ",
+    );
+    let stats = db.log_chalk_stats(|| infer_module(&db, file_id));
+
+    assert!(!stats.is_empty());
+    for stats in stats {
+        assert!(stats.fuel_consumed > 0, "{:?}", stats);
+        assert!(!stats.fuel_exhausted, "{:?}", stats);
+        assert!(!stats.panicked, "{:?}", stats);
+    }
+}
+
+#[test]
+fn cyclic_super_traits_dont_panic() {
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        trait Foo: Bar { fn foo(&self) -> u32; }
+        trait Bar: Foo {}
+        struct S;
+        impl Foo for S { fn foo(&self) -> u32 { 0 } }
+        impl Bar for S {}
+        fn test<T: Bar>(t: T) {
+            S.foo();
+            t.foo();
+        }
+    ",
+    );
+    infer_module(&db, file_id);
+}
+
+#[test]
+fn recursive_impl_terminates() {
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        trait Trait { fn foo(&self) -> u32; }
+        impl<T: Trait> Trait for T {}
+        impl Trait for dyn Trait {}
+        fn test(x: u32, y: &dyn Trait) {
+            x.foo();
+            y.foo();
+        }
+    ",
+    );
+    let stats = db.log_chalk_stats(|| infer_module(&db, file_id));
+
+    // the solver gives up once it runs out of fuel instead of looping
+    let fuel = ChalkSolverConfig::default().chalk_fuel.unwrap();
+    assert!(!stats.is_empty());
+    for stats in stats {
+        assert!(stats.fuel_consumed <= fuel, "{:?}", stats);
+        assert!(!stats.panicked, "{:?}", stats);
+    }
+}
+
+#[test]
+fn log_chalk_queries() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+        #[lang = "sized"]
+        pub trait Sized {}
+        #[lang = "unsize"]
+        pub trait Unsize<T: ?Sized> {}
+        #[lang = "coerce_unsized"]
+        pub trait CoerceUnsized<T> {}
+        #[lang = "fn_once"]
+        trait FnOnce<Args> { type Output; }
+        #[lang = "fn_mut"]
+        trait FnMut<Args>: FnOnce<Args> {}
+        #[lang = "fn"]
+        trait Fn<Args>: FnMut<Args> {}
+        #[lang = "owned_box"]
+        struct Box<T: ?Sized>(*mut T);
+        impl<T> Box<T> {
+            fn new(t: T) -> Self { loop {} }
+        }
+        impl<T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<Box<U>> for Box<T> {}
+        fn test() {
+            let _: Box<dyn Fn()> = Box::new(|| {});
+        }
+    "#,
+    );
+    let goals = db.log_chalk_queries(|| infer_module(&db, file_id));
+
+    assert!(goals.iter().any(|goal| goal.contains("Fn")), "{:#?}", goals);
+}
+
+#[test]
+fn primitive_marker_trait_goals_skip_chalk() {
+    use crate::{
+        primitive::IntTy, traits::TraitEnvironment, Canonical, InEnvironment, Obligation, Substs,
+        TraitRef,
+    };
+
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+        #[lang = "copy"]
+        trait Copy {}
+        impl Copy for i32 {}
+        #[lang = "clone"]
+        trait Clone {}
+    "#,
+    );
+    let module = db.module_for_file(file_id);
+    let goal_for = |trait_name: &str| {
+        let i32_ty = Ty::simple(TypeCtor::Int(IntTy::i32()));
+        let trait_ref =
+            TraitRef { trait_: db.item_named(module, trait_name), substs: Substs::single(i32_ty) };
+        Canonical::new(
+            InEnvironment::new(Arc::new(TraitEnvironment::default()), Obligation::Trait(trait_ref)),
+            std::iter::empty(),
+        )
+    };
+
+    let stats = db.log_chalk_stats(|| {
+        let solution = db.trait_solve(module.krate, goal_for("Copy"));
+        assert!(matches!(solution, Some(Solution::Unique(_))), "{:?}", solution);
+    });
+    assert_eq!(stats, Vec::new());
+
+    // there's no `impl Clone for i32`, so this is up to Chalk
+    let stats = db.log_chalk_stats(|| {
+        db.trait_solve(module.krate, goal_for("Clone"));
+    });
+    assert_eq!(stats.len(), 1);
+}
+
+/// Solves `S: Trait` with the given solver config. `S` isn't a primitive
+/// type, so this always goes through Chalk.
+fn solve_with_config(config: ChalkSolverConfig) -> Option<Solution> {
+    use crate::{traits::TraitEnvironment, Canonical, InEnvironment, Obligation, Substs, TraitRef};
+
+    let (mut db, file_id) = TestDB::with_single_file(
+        r#"
+        trait Other {}
+        trait Trait {}
+        struct S;
+        impl Other for S {}
+        impl<T: Other> Trait for T {}
+    "#,
+    );
+    db.set_chalk_solver_config(config);
+    let module = db.module_for_file(file_id);
+    let trait_ = db.item_named(module, "Trait");
+    let adt = db.item_named(module, "S");
+    let trait_ref = TraitRef { trait_, substs: Substs::single(Ty::simple(TypeCtor::Adt(adt))) };
+    let goal = Canonical::new(
+        InEnvironment::new(Arc::new(TraitEnvironment::default()), Obligation::Trait(trait_ref)),
+        std::iter::empty(),
+    );
+    db.trait_solve(module.krate, goal)
+}
+
+#[test]
+fn default_chalk_fuel_solves_goals() {
+    let solution = solve_with_config(ChalkSolverConfig::default());
+    assert!(matches!(solution, Some(Solution::Unique(_))), "{:?}", solution);
+}
+
+#[test]
+fn exhausted_chalk_fuel_overflows() {
+    let solution = solve_with_config(ChalkSolverConfig { chalk_fuel: Some(1) });
+    assert_eq!(solution, Some(Solution::Overflow));
+}
+
+#[test]
+fn type_ctor_num_ty_params() {
+    use hir_def::{expr::Expr, type_ref::Mutability, EnumId, FunctionId, StructId, TraitId};
+
+    use crate::{
+        primitive::{FloatTy, IntTy},
+        CallableDef, OpaqueTyId, TypeCtor,
+    };
+    let (db, file_id) = TestDB::with_single_file(
+        "
+        struct S<T, U>;
+        enum E { A }
+        trait Trait<X> { type Assoc; }
+        fn foo<T>() -> impl Sized { |x: u32| x; }
+    ",
+    );
+    let module = db.module_for_file(file_id);
+    let struct_: StructId = db.item_named(module, "S");
+    let enum_: EnumId = db.item_named(module, "E");
+    let trait_: TraitId = db.item_named(module, "Trait");
+    let func: FunctionId = db.item_named(module, "foo");
+    let assoc_ty = db.trait_data(trait_).associated_types().next().unwrap();
+    let body = db.body(func.into());
+    let closure_expr = body
+        .exprs
+        .iter()
+        .find_map(|(id, expr)| if let Expr::Lambda { .. } = expr { Some(id) } else { None })
+        .unwrap();
+
+    let cases = [
+        (TypeCtor::Bool, 0),
+        (TypeCtor::Char, 0),
+        (TypeCtor::Int(IntTy::i32()), 0),
+        (TypeCtor::Float(FloatTy::f64()), 0),
+        (TypeCtor::Str, 0),
+        (TypeCtor::Never, 0),
+        (TypeCtor::Slice, 1),
+        (TypeCtor::Array, 1),
+        (TypeCtor::RawPtr(Mutability::Shared), 1),
+        (TypeCtor::Ref(Mutability::Mut), 1),
+        (TypeCtor::Adt(struct_.into()), 2),
+        (TypeCtor::Adt(enum_.into()), 0),
+        (TypeCtor::FnDef(CallableDef::FunctionId(func)), 1),
+        (TypeCtor::FnDef(CallableDef::StructId(struct_)), 2),
+        (TypeCtor::FnPtr { num_args: 0 }, 1),
+        (TypeCtor::FnPtr { num_args: 2 }, 3),
+        (TypeCtor::Tuple { cardinality: 0 }, 0),
+        (TypeCtor::Tuple { cardinality: 3 }, 3),
+        // the trait's `Self` and `X`
+        (TypeCtor::AssociatedType(assoc_ty), 2),
+        (TypeCtor::OpaqueType(OpaqueTyId::ReturnTypeImplTrait(func, 0)), 1),
+        (TypeCtor::Closure { def: func.into(), expr: closure_expr }, 1),
+    ];
+    for (type_ctor, expected) in cases.iter() {
+        assert_eq!(type_ctor.num_ty_params(&db), *expected, "{:?}", type_ctor);
+    }
+}
+
+#[test]
+fn fixture_files_by_path() {
+    let (db, files) = TestDB::with_files_by_path(
+        r#"
+//- /main.rs crate:main deps:dep
+mod foo;
+//- /foo.rs
+fn foo() {}
+//- /lib.rs crate:dep
+fn bar() {}
+"#,
+    );
+    assert_eq!(files.len(), 3);
+    assert!(db.file_text(files["/foo.rs"]).contains("fn foo"));
+    let main_krate = db.module_for_file(files["/main.rs"]).krate;
+    assert_eq!(db.module_for_file(files["/foo.rs"]).krate, main_krate);
+    assert_ne!(db.module_for_file(files["/lib.rs"]).krate, main_krate);
+}
+
+#[test]
+fn object_safety_violations() {
+    use crate::traits::ObjectSafetyViolation;
+
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+#[lang = "sized"]
+trait Sized {}
+trait Trait {
+    fn ok(&self) -> u32;
+    fn sized_only(&self) -> Self where Self: Sized;
+    fn static_fn();
+    fn generic<T>(&self, t: T);
+    fn returns_self(&self) -> Self;
+    const C: u32;
+}
+"#,
+    );
+    let module = db.module_for_file(file_id);
+    let trait_ = db.item_named(module, "Trait");
+
+    let violations = db.object_safety_violations(trait_);
+    let descriptions: Vec<String> = violations
+        .iter()
+        .map(|violation| match violation {
+            ObjectSafetyViolation::StaticMethod(f) => {
+                format!("static {}", db.function_data(*f).name)
+            }
+            ObjectSafetyViolation::GenericMethod(f) => {
+                format!("generic {}", db.function_data(*f).name)
+            }
+            ObjectSafetyViolation::SelfInSignature(f) => {
+                format!("self {}", db.function_data(*f).name)
+            }
+            other => format!("{:?}", other).split('(').next().unwrap().to_string(),
+        })
+        .collect();
+    assert_eq!(
+        descriptions,
+        vec!["static static_fn", "generic generic", "self returns_self", "AssocConst"]
+    );
+}
+
+#[test]
+fn ty_from_str() {
+    let (db, file_id) = TestDB::with_single_file("struct Vec<T>(T);");
+    let krate = db.module_for_file(file_id).krate;
+
+    let ty = Ty::try_from_str(&db, krate, "Vec<(i32, &str)>").unwrap();
+    assert_eq!(ty.display(&db).to_string(), "Vec<(i32, &str)>");
+    let err = Ty::try_from_str(&db, krate, "Vec<").unwrap_err();
+    assert!(!err.errors.is_empty());
+    assert!(err.errors.iter().all(|it| it.range().end() <= TextSize::of("Vec<")), "{}", err);
+    let err = Ty::try_from_str(&db, krate, "i32; struct S").unwrap_err();
+    assert_eq!(err.to_string(), "0..13: expected a single type");
+}
+
+#[test]
+fn collect_tyconstrs() {
+    let (db, file_id) = TestDB::with_single_file("struct Vec<T>(T);");
+    let krate = db.module_for_file(file_id).krate;
+
+    let ty = Ty::try_from_str(&db, krate, "Vec<(i32, &Vec<i32>)>").unwrap();
+    let ctors = ty.collect_tyconstrs();
+    assert_eq!(ctors.len(), 4);
+    assert!(ctors.iter().any(|ctor| matches!(ctor, TypeCtor::Adt(_))));
+    assert!(ctors.iter().any(|ctor| matches!(ctor, TypeCtor::Int(_))));
+}
+
+#[test]
+fn impl_trait_for() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+trait Clone {}
+trait Trait {}
+struct S<T>(T);
+struct V<T>(T);
+impl Trait for S<u32> {}
+impl<T: Clone> Trait for V<T> {}
+"#,
+    );
+    let krate = db.module_for_file(file_id).krate;
+
+    assert!(db.impl_trait_for(krate, "S<u32>", "Trait").is_some());
+    assert!(db.impl_trait_for(krate, "S<i32>", "Trait").is_none());
+    let generic_impl = db.impl_trait_for(krate, "V<i32>", "Trait").unwrap();
+    assert_eq!(db.generic_predicates(generic_impl.into()).len(), 1);
+}
+
+#[test]
+fn test_db_builder_wires_crate_graph() {
+    let db = TestDBBuilder::new()
+        .with_crate(
+            "std",
+            r#"
+            pub trait Trait {}
+            pub struct StdStruct;
+            impl Trait for StdStruct {}
+            "#,
+        )
+        .with_crate(
+            "mycrate",
+            r#"
+            struct MyStruct;
+            impl std::Trait for MyStruct {}
+            "#,
+        )
+        .build();
+    let std = db.crate_named("std");
+    let mycrate = db.crate_named("mycrate");
+    assert_eq!(db.crate_graph()[mycrate].dependencies.len(), 1);
+
+    db.check_impl_count(std, "Trait", 1);
+    db.check_impl_count(mycrate, "Trait", 2);
+    assert!(db.impl_trait_for(mycrate, "std::StdStruct", "Trait").is_some());
+    assert!(db.impl_trait_for(mycrate, "MyStruct", "Trait").is_some());
+}
+
+#[test]
+fn type_annotations() {
+    let fixture = r#"
+struct Vec<T>(T);
+fn test(v: Vec<i32>) {
+    v;
+  //^ type: Vec<i32>
+}
+"#;
+    check_types(fixture);
+
+    let (db, file_id) = TestDB::with_single_file(fixture);
+    let offset = TextSize::from(db.file_text(file_id).find("v;").unwrap() as u32);
+    assert_eq!(db.extract_type_at(file_id, offset).as_deref(), Some("Vec<i32>"));
+    assert_eq!(db.extract_type_at(file_id, 0.into()), None);
+}
+
+#[test]
+#[should_panic(expected = "text differs")]
+fn check_infer_reports_mismatches() {
+    TestDB::with_files(
+        r#"
+fn test() {
+    let a = 1u32;
+    a;
+  //^ u32
+    true;
+  //^^^^ i32
+}
+"#,
+    )
+    .check_infer();
+}
+
 #[test]
 fn no_such_field_diagnostics() {
     let diagnostics = TestDB::with_files(
@@ -369,9 +894,55 @@ fn no_such_field_diagnostics() {
     );
 }
 
+#[test]
+fn const_and_static_diagnostics() {
+    let db = TestDB::with_files(
+        r"
+        //- /lib.rs
+        struct S { foo: i32 }
+        const C: S = S { foo: 1, bar: 2 };
+        static ST: S = S { foo: 1, baz: 3 };
+        struct T;
+        impl T {
+            const A: S = S { foo: 1, qux: 4 };
+        }
+        ",
+    );
+
+    db.assert_diagnostic_count(3);
+    let diagnostics = db.diagnostics().0;
+    assert!(diagnostics.contains("\"bar: 2\": no such field"));
+    assert!(diagnostics.contains("\"baz: 3\": no such field"));
+    assert!(diagnostics.contains("\"qux: 4\": no such field"));
+}
+
+#[test]
+fn module_diagnostics() {
+    let (db, file_id) = TestDB::with_single_file(
+        r"
+        struct S { foo: i32 }
+        fn helper() -> S { S { foo: 1, bar: 2 } }
+        mod foo {
+            mod bar {
+                fn f() -> crate::S { crate::S { foo: 1, baz: 3 } }
+            }
+        }
+        ",
+    );
+
+    db.assert_diagnostic_count(2);
+    let foo = db.module_for_path(file_id, "foo");
+    assert_eq!(db.module_diagnostics(foo).1, 0);
+    let bar = db.module_for_path(file_id, "foo::bar");
+    assert_snapshot!(db.module_diagnostics(bar).0, @r###"
+    "baz: 3": no such field
+    "###
+    );
+}
+
 #[test]
 fn no_such_field_with_feature_flag_diagnostics() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r#"
         //- /lib.rs crate:foo cfg:feature=foo
         struct MyStruct {
@@ -393,15 +964,12 @@ fn no_such_field_with_feature_flag_diagnostics() {
         }
         "#,
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @r###""###);
+    .assert_no_diagnostics();
 }
 
 #[test]
 fn no_such_field_enum_with_feature_flag_diagnostics() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r#"
         //- /lib.rs crate:foo cfg:feature=foo
         enum Foo {
@@ -420,15 +988,12 @@ fn no_such_field_enum_with_feature_flag_diagnostics() {
         }
         "#,
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @r###""###);
+    .assert_no_diagnostics();
 }
 
 #[test]
 fn no_such_field_with_feature_flag_diagnostics_on_struct_lit() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r#"
         //- /lib.rs crate:foo cfg:feature=foo
         struct S {
@@ -450,15 +1015,12 @@ fn no_such_field_with_feature_flag_diagnostics_on_struct_lit() {
         }
         "#,
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @r###""###);
+    .assert_no_diagnostics();
 }
 
 #[test]
 fn no_such_field_with_feature_flag_diagnostics_on_block_expr() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r#"
         //- /lib.rs crate:foo cfg:feature=foo
         struct S {
@@ -482,15 +1044,12 @@ fn no_such_field_with_feature_flag_diagnostics_on_block_expr() {
         }
         "#,
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @r###""###);
+    .assert_no_diagnostics();
 }
 
 #[test]
 fn no_such_field_with_feature_flag_diagnostics_on_struct_fields() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r#"
         //- /lib.rs crate:foo cfg:feature=foo
         struct S {
@@ -512,15 +1071,12 @@ fn no_such_field_with_feature_flag_diagnostics_on_struct_fields() {
         }
         "#,
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @r###""###);
+    .assert_no_diagnostics();
 }
 
 #[test]
 fn no_such_field_with_type_macro() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r"
         macro_rules! Type {
             () => { u32 };
@@ -536,10 +1092,7 @@ fn no_such_field_with_type_macro() {
         }
         ",
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @r###""###);
+    .assert_no_diagnostics();
 }
 
 #[test]
@@ -565,7 +1118,7 @@ fn missing_record_pat_field_diagnostic() {
 
 #[test]
 fn missing_record_pat_field_no_diagnostic_if_not_exhaustive() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r"
         //- /lib.rs
         struct S { foo: i32, bar: () }
@@ -576,10 +1129,7 @@ fn missing_record_pat_field_no_diagnostic_if_not_exhaustive() {
         }
         ",
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @"");
+    .assert_no_diagnostics();
 }
 
 #[test]
@@ -646,8 +1196,27 @@ fn missing_unsafe() {
 }
 
 #[test]
-fn no_missing_unsafe_diagnostic_with_raw_ptr_in_unsafe_block() {
+fn missing_unsafe_diagnostic_with_target_feature_call() {
     let diagnostics = TestDB::with_files(
+        r#"
+#[target_feature(enable = "avx2")]
+fn avx2_fn() {}
+
+fn missing_unsafe() {
+    avx2_fn();
+    unsafe { avx2_fn() };
+}
+"#,
+    )
+    .diagnostics()
+    .0;
+
+    assert_snapshot!(diagnostics, @r#""avx2_fn()": This operation is unsafe and requires an unsafe function or block"#);
+}
+
+#[test]
+fn no_missing_unsafe_diagnostic_with_raw_ptr_in_unsafe_block() {
+    TestDB::with_files(
         r"
 fn nothing_to_see_move_along() {
     let x = &5 as *const usize;
@@ -657,10 +1226,7 @@ fn nothing_to_see_move_along() {
 }
 ",
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @"");
+    .assert_no_diagnostics();
 }
 
 #[test]
@@ -684,7 +1250,7 @@ fn nothing_to_see_move_along() {
 
 #[test]
 fn no_missing_unsafe_diagnostic_with_unsafe_call_in_unsafe_block() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r"
 unsafe fn unsafe_fn() {
     let x = &5 as *const usize;
@@ -698,15 +1264,12 @@ fn nothing_to_see_move_along() {
 }
 ",
     )
-    .diagnostics()
-    .0;
-
-    assert_snapshot!(diagnostics, @"");
+    .assert_no_diagnostics();
 }
 
 #[test]
 fn no_missing_unsafe_diagnostic_with_unsafe_method_call_in_unsafe_block() {
-    let diagnostics = TestDB::with_files(
+    TestDB::with_files(
         r"
 struct HasUnsafe;
 
@@ -723,12 +1286,42 @@ fn nothing_to_see_move_along() {
     }
 }
 
+",
+    )
+    .assert_no_diagnostics();
+}
+
+#[test]
+fn missing_unsafe_diagnostic_with_union_field_access() {
+    let diagnostics = TestDB::with_files(
+        r"
+union U { a: u32, b: f32 }
+
+fn missing_unsafe(u: U) {
+    let x = u.a;
+    let y = unsafe { u.b };
+}
 ",
     )
     .diagnostics()
     .0;
 
-    assert_snapshot!(diagnostics, @"");
+    assert_snapshot!(diagnostics, @r#""u.a": This operation is unsafe and requires an unsafe function or block"#);
+}
+
+#[test]
+fn no_missing_unsafe_diagnostic_with_union_field_assignment() {
+    TestDB::with_files(
+        r"
+union U { a: u32, b: f32 }
+
+fn assign(mut u: U) {
+    u.a = 1;
+    u.b = unsafe { u.b };
+}
+",
+    )
+    .assert_no_diagnostics();
 }
 
 #[test]
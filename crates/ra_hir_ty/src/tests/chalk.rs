@@ -0,0 +1,730 @@
+use chalk_ir::{cast::Cast, TypeName};
+use chalk_solve::{rust_ir, RustIrDatabase};
+use hir_def::{db::DefDatabase, AssocItemId, HasModule, Lookup, ModuleDefId, ModuleId};
+use ra_db::{fixture::WithFixture, CrateId};
+use rustc_hash::FxHashSet;
+
+use crate::{
+    db::HirDatabase,
+    display::HirDisplay,
+    test_db::{TestDB, TestDBBuilder},
+    traits::{
+        chalk::{
+            from_chalk, impl_datum_recover, is_coinductive_trait, trait_datum_recover, AdtId,
+            FnAbi, Interner, OpaqueTyId, ToChalk,
+        },
+        ChalkContext, Impl,
+    },
+    BoundVar, DebruijnIndex, GenericPredicate, Substs, Ty, TypeCtor,
+};
+
+fn single_file_module(ra_fixture: &str) -> (TestDB, ModuleId) {
+    let (db, file_id) = TestDB::with_single_file(ra_fixture);
+    let module = db.module_for_file(file_id);
+    (db, module)
+}
+
+fn with_chalk_context<R>(db: &TestDB, krate: CrateId, f: impl FnOnce(&ChalkContext<'_>) -> R) -> R {
+    let db: &dyn HirDatabase = db;
+    db.with_chalk_context(krate, f)
+}
+
+#[test]
+fn local_impls_are_checked_for_coherence() {
+    let (db, files) = TestDB::with_files_by_path(
+        r#"
+//- /main.rs crate:main deps:dep
+use dep::Trait;
+struct S;
+impl Trait for S {}
+impl<T> Trait for T {}
+//- /lib.rs crate:dep
+pub trait Trait {}
+impl<T> Trait for T {}
+"#,
+    );
+    let module = db.module_for_file(files["/main.rs"]);
+    let trait_: hir_def::TraitId = db.item_named(module, "Trait");
+
+    let impls = with_chalk_context(&db, module.krate, |context| {
+        context.local_impls_to_coherence_check(trait_.to_chalk(&db))
+    });
+    assert_eq!(impls.len(), 2);
+    for impl_ in impls {
+        let impl_: Impl = from_chalk(&db, impl_);
+        let impl_id = match impl_ {
+            Impl::ImplDef(it) => it,
+            _ => panic!("unexpected impl {:?}", impl_),
+        };
+        assert_eq!(impl_id.lookup(&db).container.module(&db).krate, module.krate);
+    }
+}
+
+#[test]
+fn cycle_recovery_datums() {
+    let (db, files) = TestDB::with_files_by_path(
+        r#"
+//- /main.rs crate:main deps:dep
+use dep::Trait;
+struct S;
+impl Trait for S {}
+//- /lib.rs crate:dep
+pub trait Trait {}
+"#,
+    );
+    let module = db.module_for_file(files["/main.rs"]);
+    let trait_: hir_def::TraitId = db.item_named(module, "Trait");
+
+    let trait_datum = trait_datum_recover(&db, &[], &module.krate, &trait_.to_chalk(&db));
+    assert!(trait_datum.flags.upstream);
+
+    let impls = db.all_chalk_impls(module.krate);
+    assert_eq!(impls.len(), 1);
+    // the recovered impl must not make `S: Trait` (or anything else) hold
+    let impl_datum = impl_datum_recover(&db, &[], &module.krate, &impls[0]);
+    assert_eq!(impl_datum.polarity, rust_ir::Polarity::Negative);
+}
+
+#[test]
+fn impl_datum_for_all_impls() {
+    let (db, files) = TestDB::with_files_by_path(
+        r#"
+//- /main.rs crate:main deps:dep
+use dep::Trait;
+trait Assoc { type Item; }
+struct S<T>(T);
+impl<T: Trait> Assoc for S<T> { type Item = T; }
+impl Trait for S<u32> {}
+impl S<u32> {}
+//- /lib.rs crate:dep
+pub trait Trait {}
+impl Trait for i32 {}
+"#,
+    );
+    let krate = db.module_for_file(files["/main.rs"]).krate;
+
+    db.check_impl_count(krate, "Assoc", 1);
+    db.check_impl_count_gte(krate, "Trait", 2);
+
+    let impls = db.all_chalk_impls(krate);
+    // the inherent impl doesn't count
+    assert_eq!(impls.len(), 3);
+    assert_eq!(db.duplicate_impl_datums(krate), vec![]);
+}
+
+#[test]
+fn duplicate_impl_datums() {
+    let (db, module) = single_file_module(
+        r#"
+trait Trait {}
+struct S;
+impl Trait for S {}
+impl Trait for S {}
+impl Trait for u32 {}
+"#,
+    );
+    let krate = module.krate;
+    let impls = db.all_chalk_impls(krate);
+    assert_eq!(db.duplicate_impl_datums(krate), vec![(impls[0], impls[1])]);
+}
+
+#[test]
+fn all_explicit_impls_in_krate() {
+    let (db, module) = single_file_module(
+        r#"
+#[lang = "fn_once"]
+trait FnOnce<Args> { type Output; }
+trait Trait {}
+struct S;
+impl S {}
+impl Trait for S {}
+impl Unresolved for S {}
+fn test() { let f = || 1; }
+"#,
+    );
+    let krate = module.krate;
+
+    let impls = db.all_explicit_impls_in_krate(krate);
+    assert_eq!(impls.len(), 3);
+    for impl_id in impls.iter() {
+        assert_eq!(impl_id.lookup(&db).container.module(&db).krate, krate);
+    }
+}
+
+#[test]
+fn run_chalk_query_trait_goal() {
+    let (db, module) = single_file_module(
+        r#"
+trait Trait {}
+struct S;
+struct T;
+impl Trait for S {}
+"#,
+    );
+    let trait_ = db.item_named(module, "Trait");
+    let goal_for = |name: &str| {
+        let strukt: hir_def::StructId = db.item_named(module, name);
+        let self_ty = Ty::simple(TypeCtor::Adt(strukt.into()));
+        let trait_ref = crate::TraitRef { trait_, substs: Substs::single(self_ty) };
+        let goal: chalk_ir::Goal<Interner> = trait_ref.to_chalk(&db).cast(&Interner);
+        goal
+    };
+
+    let solution = db.run_chalk_query(module.krate, goal_for("S"));
+    assert!(solution.map_or(false, |it| it.is_unique()));
+    assert!(db.run_chalk_query(module.krate, goal_for("T")).is_none());
+}
+
+#[test]
+fn closure_kinds_and_upvars() {
+    let (db, module) = single_file_module(
+        r#"
+struct S;
+fn test() {
+let x = 1;
+let mut y = 2;
+let s = S;
+let f = || x + 1;
+let f_mut = || { y += x; };
+let f_once = || { let t = s; };
+}
+"#,
+    );
+    let func: hir_def::FunctionId = db.item_named(module, "test");
+    let def: hir_def::DefWithBodyId = func.into();
+    let body = db.body(def);
+    let closures: Vec<_> = body
+        .exprs
+        .iter()
+        .filter_map(|(id, expr)| match expr {
+            hir_def::expr::Expr::Lambda { .. } => Some(id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(closures.len(), 3);
+
+    let (kinds, upvars) = with_chalk_context(&db, module.krate, |context| {
+        let substs = chalk_ir::Substitution::empty(&Interner);
+        let ids = closures.iter().map(|expr| (def, *expr).to_chalk(&db));
+        let kinds: Vec<_> = ids.clone().map(|id| context.closure_kind(id, &substs)).collect();
+        let upvars: Vec<_> = ids
+            .map(|id| {
+                let upvars = context.closure_upvars(id, &substs);
+                let ty: Ty = from_chalk(&db, upvars.skip_binders().clone());
+                ty.display(&db).to_string()
+            })
+            .collect();
+        (kinds, upvars)
+    });
+    assert_eq!(
+        kinds,
+        vec![rust_ir::ClosureKind::Fn, rust_ir::ClosureKind::FnMut, rust_ir::ClosureKind::FnOnce]
+    );
+    assert_eq!(upvars, vec!["(&i32,)", "(&mut i32, &i32)", "(S,)"]);
+}
+
+#[test]
+fn object_safety() {
+    let (db, module) = single_file_module(
+        r#"
+#[lang = "sized"]
+trait Sized {}
+trait Iterator { type Item; fn next(&mut self) -> Self::Item; }
+trait Clone { fn clone(&self) -> Self; }
+trait SubClone: Clone {}
+#[marker]
+trait Marker {}
+trait Rpit { fn f(&self) -> impl Sized; }
+"#,
+    );
+    let traits: Vec<hir_def::TraitId> = db.module_items(module);
+
+    let mut results: Vec<(String, bool)> = with_chalk_context(&db, module.krate, |context| {
+        traits
+            .iter()
+            .map(|&it| {
+                let name = db.trait_data(it).name.to_string();
+                (name, context.is_object_safe(it.to_chalk(&db)))
+            })
+            .filter(|(name, _)| name != "Sized")
+            .collect()
+    });
+    results.sort();
+    let expected = [
+        ("Clone", false),
+        ("Iterator", true),
+        ("Marker", true),
+        ("Rpit", false),
+        ("SubClone", false),
+    ];
+    let expected: Vec<(String, bool)> =
+        expected.iter().map(|(name, safe)| (name.to_string(), *safe)).collect();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn impl_provided_for_explicit_auto_trait_impls() {
+    let (db, module) = single_file_module(
+        r#"
+auto trait Send {}
+struct MyStruct;
+struct Other;
+impl !Send for MyStruct {}
+impl<T> Send for (T,) {}
+"#,
+    );
+    let send: hir_def::TraitId = db.item_named(module, "Send");
+    let adt_id = |name: &str| -> AdtId {
+        let adt: hir_def::AdtId = db.item_named(module, name);
+        db.intern_type_ctor(TypeCtor::Adt(adt)).into()
+    };
+
+    let (my_struct, other) = with_chalk_context(&db, module.krate, |context| {
+        let send = send.to_chalk(&db);
+        (
+            context.impl_provided_for(send, adt_id("MyStruct")),
+            context.impl_provided_for(send, adt_id("Other")),
+        )
+    });
+    assert!(my_struct);
+    assert!(!other);
+}
+
+#[test]
+fn item_names() {
+    let (db, module) = single_file_module(
+        r#"
+trait Trait { type Assoc; }
+struct S;
+enum E { V(u32) }
+fn foo() -> impl Trait {}
+"#,
+    );
+    let mut names = Vec::new();
+    with_chalk_context(&db, module.krate, |context| {
+        for decl in db.module_items::<ModuleDefId>(module) {
+            match decl {
+                ModuleDefId::TraitId(it) => {
+                    names.push(context.trait_name(it.to_chalk(&db)));
+                    let assoc = match db.trait_data(it).items[0].1 {
+                        AssocItemId::TypeAliasId(it) => it,
+                        item => panic!("unexpected item {:?}", item),
+                    };
+                    names.push(context.assoc_type_name(assoc.to_chalk(&db)));
+                }
+                ModuleDefId::AdtId(it) => {
+                    let adt_id: AdtId = db.intern_type_ctor(TypeCtor::Adt(it)).into();
+                    names.push(context.adt_name(adt_id));
+                    if let hir_def::AdtId::EnumId(e) = it {
+                        let (local_id, _) = db.enum_data(e).variants.iter().next().unwrap();
+                        let variant = hir_def::EnumVariantId { parent: e, local_id };
+                        let fn_def = variant.to_chalk(&db);
+                        names.push(context.fn_def_name(fn_def));
+                    }
+                }
+                ModuleDefId::FunctionId(it) => {
+                    names.push(context.fn_def_name(it.to_chalk(&db)));
+                    let opaque_ty = crate::OpaqueTyId::ReturnTypeImplTrait(it, 0);
+                    names.push(context.opaque_type_name(opaque_ty.to_chalk(&db)));
+                }
+                _ => {}
+            }
+        }
+    });
+    names.sort();
+    assert_eq!(names, vec!["Assoc", "E", "S", "Trait", "V", "foo", "{impl trait 0 of foo}"]);
+}
+
+#[test]
+fn adt_reprs() {
+    let (db, module) = single_file_module(
+        r#"
+#[repr(C)]
+struct C;
+#[repr(packed)]
+struct Packed;
+#[repr(C, packed(2))]
+struct Both;
+#[repr(u8)]
+enum Plain { A }
+"#,
+    );
+    let adts: Vec<hir_def::AdtId> = db.module_items(module);
+    let mut reprs: Vec<(String, bool, bool)> = with_chalk_context(&db, module.krate, |context| {
+        adts.iter()
+            .map(|&it| {
+                let adt_id: AdtId = db.intern_type_ctor(TypeCtor::Adt(it)).into();
+                let repr = context.adt_repr(adt_id);
+                (context.adt_name(adt_id), repr.repr_c, repr.repr_packed)
+            })
+            .collect()
+    });
+    reprs.sort();
+    assert_eq!(
+        reprs,
+        vec![
+            ("Both".to_string(), true, true),
+            ("C".to_string(), true, false),
+            ("Packed".to_string(), false, true),
+            ("Plain".to_string(), false, false),
+        ]
+    );
+}
+
+#[test]
+fn marker_trait_flag() {
+    let (db, module) = single_file_module(
+        r#"
+#[marker]
+trait Marker {}
+trait NotMarker {}
+"#,
+    );
+    let mut flags: Vec<(String, bool)> = db
+        .module_items(module)
+        .into_iter()
+        .map(|it: hir_def::TraitId| {
+            let datum = db.trait_datum(module.krate, it.to_chalk(&db));
+            (db.trait_data(it).name.to_string(), datum.flags.marker)
+        })
+        .collect();
+    flags.sort();
+    assert_eq!(flags, vec![("Marker".to_string(), true), ("NotMarker".to_string(), false)]);
+}
+
+#[test]
+fn coinductive_trait_flag() {
+    let (db, module) = single_file_module(
+        r#"
+#[rustc_coinductive]
+trait Coinductive {}
+trait Inductive {}
+auto trait Auto {}
+"#,
+    );
+    let mut flags: Vec<(String, bool)> = db
+        .module_items(module)
+        .into_iter()
+        .map(|it: hir_def::TraitId| {
+            let datum = db.trait_datum(module.krate, it.to_chalk(&db));
+            assert_eq!(datum.flags.coinductive, is_coinductive_trait(&db, it));
+            (db.trait_data(it).name.to_string(), datum.flags.coinductive)
+        })
+        .collect();
+    flags.sort();
+    assert_eq!(
+        flags,
+        vec![
+            ("Auto".to_string(), false),
+            ("Coinductive".to_string(), true),
+            ("Inductive".to_string(), false),
+        ]
+    );
+}
+
+#[test]
+fn opaque_ty_ids_are_distinct() {
+    let db = TestDBBuilder::new()
+        .with_crate(
+            "a",
+            r#"
+            pub trait Trait {}
+            impl Trait for () {}
+            pub fn f() -> impl Trait {}
+            "#,
+        )
+        .with_crate(
+            "b",
+            r#"
+            pub fn f() -> impl a::Trait {}
+            pub fn g() -> impl a::Trait {}
+            "#,
+        )
+        .build();
+    let mut funcs: Vec<hir_def::FunctionId> = Vec::new();
+    for name in &["a", "b"] {
+        let krate = db.crate_named(name);
+        let root = db.crate_def_map(krate).root;
+        funcs.extend(db.module_items(ModuleId { krate, local_id: root }));
+    }
+    assert_eq!(funcs.len(), 3);
+
+    let ids: Vec<OpaqueTyId> = funcs
+        .iter()
+        .map(|&func| {
+            let opaque_ty_id = crate::OpaqueTyId::ReturnTypeImplTrait(func, 0);
+            let id = opaque_ty_id.to_chalk(&db);
+            let back: crate::OpaqueTyId = from_chalk(&db, id);
+            assert_eq!(back, opaque_ty_id);
+            assert_eq!(db.opaque_ty_datum(id).opaque_ty_id, id);
+            id
+        })
+        .collect();
+    let distinct: FxHashSet<_> = ids.iter().collect();
+    assert_eq!(distinct.len(), ids.len(), "{:?}", ids);
+}
+
+#[test]
+fn opaque_ty_datum_binders() {
+    let (db, module) = single_file_module(
+        r#"
+trait Clone {}
+trait Trait<T> {}
+fn foo<T: Clone>() -> impl Clone { loop {} }
+fn bar<T>() -> impl Trait<T> { loop {} }
+"#,
+    );
+    let func = |name: &str| db.item_named(module, name);
+    let bound_var = |debruijn, idx| {
+        chalk_ir::TyData::BoundVar(BoundVar::new(DebruijnIndex::new(debruijn), idx))
+            .intern(&Interner)
+    };
+    let where_clause_params = |func: hir_def::FunctionId| {
+        let id = crate::OpaqueTyId::ReturnTypeImplTrait(func, 0).to_chalk(&db);
+        let datum = db.opaque_ty_datum(id);
+        // one binder for the function's type parameter...
+        assert_eq!(datum.bound.len(&Interner), 1);
+        // ... and one for the opaque type itself
+        let bounds = &datum.bound.skip_binders().bounds;
+        assert_eq!(bounds.len(&Interner), 1);
+        assert_eq!(bounds.skip_binders().len(), 1);
+        match bounds.skip_binders()[0].skip_binders() {
+            chalk_ir::WhereClause::Implemented(trait_ref) => trait_ref
+                .substitution
+                .iter(&Interner)
+                .map(|arg| arg.assert_ty_ref(&Interner).clone())
+                .collect::<Vec<_>>(),
+            where_clause => panic!("unexpected where clause {:?}", where_clause),
+        }
+    };
+    // Inside the where clause's own (empty) binder, the opaque type is one
+    // level out and the function's type parameters are two levels out.
+    assert_eq!(where_clause_params(func("foo")), vec![bound_var(1, 0)]);
+    assert_eq!(where_clause_params(func("bar")), vec![bound_var(1, 0), bound_var(2, 0)]);
+}
+
+#[test]
+fn hidden_opaque_type_of_recursive_fn() {
+    let (db, module) = single_file_module(
+        r#"
+trait Trait {}
+impl Trait for u32 {}
+fn f(n: u32) -> impl Trait {
+    if n == 0 {
+        return 0u32;
+    }
+    f(n - 1)
+}
+"#,
+    );
+    let func: hir_def::FunctionId = db.item_named(module, "f");
+    // the hidden type comes from the `return`, not from the recursive call
+    let hidden = with_chalk_context(&db, module.krate, |context| {
+        let id = crate::OpaqueTyId::ReturnTypeImplTrait(func, 0).to_chalk(&db);
+        let ty: Ty = from_chalk(&db, context.hidden_opaque_type(id));
+        ty.display(&db).to_string()
+    });
+    assert_eq!(hidden, "u32");
+}
+
+#[test]
+fn adt_flags() {
+    let (db, module) = single_file_module(
+        r#"
+#[fundamental]
+struct Box<T>(T);
+#[lang = "phantom_data"]
+struct PhantomData<T>;
+struct Plain;
+"#,
+    );
+    let mut flags: Vec<(String, bool, bool)> = db
+        .module_items(module)
+        .into_iter()
+        .map(|it: hir_def::StructId| {
+            let struct_id: AdtId = db.intern_type_ctor(TypeCtor::Adt(it.into())).into();
+            let datum = db.struct_datum(module.krate, struct_id);
+            (db.struct_data(it).name.to_string(), datum.flags.fundamental, datum.flags.phantom_data)
+        })
+        .collect();
+    flags.sort();
+    assert_eq!(
+        flags,
+        vec![
+            ("Box".to_string(), true, false),
+            ("PhantomData".to_string(), false, true),
+            ("Plain".to_string(), false, false),
+        ]
+    );
+}
+
+#[test]
+fn adt_kinds() {
+    let (db, module) = single_file_module(
+        r#"
+struct S;
+enum E { A, B }
+union U { a: u32 }
+"#,
+    );
+    let mut kinds: Vec<(&str, &str)> = db
+        .module_items(module)
+        .into_iter()
+        .map(|adt: hir_def::AdtId| {
+            let struct_id: AdtId = db.intern_type_ctor(TypeCtor::Adt(adt)).into();
+            let type_ctor: TypeCtor = from_chalk(&db, TypeName::Adt(struct_id));
+            assert_eq!(type_ctor, TypeCtor::Adt(adt));
+            let expected = match adt {
+                hir_def::AdtId::StructId(_) => "struct",
+                hir_def::AdtId::EnumId(_) => "enum",
+                hir_def::AdtId::UnionId(_) => "union",
+            };
+            let kind = match db.struct_datum(module.krate, struct_id).kind {
+                rust_ir::AdtKind::Struct => "struct",
+                rust_ir::AdtKind::Enum => "enum",
+                rust_ir::AdtKind::Union => "union",
+            };
+            (expected, kind)
+        })
+        .collect();
+    kinds.sort();
+    assert_eq!(kinds, vec![("enum", "enum"), ("struct", "struct"), ("union", "union")]);
+}
+
+#[test]
+fn struct_datum_binders_skip_lifetimes() {
+    let (db, module) = single_file_module(
+        r#"
+struct S<'a, T, 'b, U>(&'a T, &'b U);
+"#,
+    );
+    let strukt = db.item_named(module, "S");
+
+    let struct_id: AdtId = db.intern_type_ctor(TypeCtor::Adt(strukt)).into();
+    let datum = db.struct_datum(module.krate, struct_id);
+    assert_eq!(datum.binders.binders.len(&Interner), 2);
+}
+
+#[test]
+fn fn_def_datum_abi_and_varargs() {
+    let (db, module) = single_file_module(
+        r#"
+extern "C" fn foo(x: i32, ...) {}
+fn bar(x: i32) {}
+extern "C" { fn baz(fmt: *const u8, ...); }
+extern "stdcall" fn qux() {}
+"#,
+    );
+    let mut results: Vec<(String, FnAbi, usize, bool)> = db
+        .module_items(module)
+        .into_iter()
+        .map(|func: hir_def::FunctionId| {
+            let data = db.function_data(func);
+            let datum = db.fn_def_datum(module.krate, func.to_chalk(&db));
+            let inputs_and_output = datum.binders.skip_binders().inputs_and_output.skip_binders();
+            (
+                data.name.to_string(),
+                datum.abi,
+                inputs_and_output.argument_types.len(),
+                data.is_varargs,
+            )
+        })
+        .collect();
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        results,
+        vec![
+            ("bar".to_string(), FnAbi::Rust, 1, false),
+            ("baz".to_string(), FnAbi::C, 1, true),
+            ("foo".to_string(), FnAbi::C, 1, true),
+            ("qux".to_string(), FnAbi::Other, 0, false),
+        ]
+    );
+}
+
+#[test]
+fn fn_def_datum_custom_self_type() {
+    let (db, files) = TestDB::with_files_by_path(
+        r#"
+//- /main.rs
+struct Box<T>(T);
+trait Foo { fn bar(self: Box<Self>); }
+"#,
+    );
+    let module = db.module_for_file(files["/main.rs"]);
+    let trait_ = db.item_named(module, "Foo");
+    let func = match db.trait_data(trait_).items[0].1 {
+        AssocItemId::FunctionId(it) => it,
+        item => panic!("unexpected item {:?}", item),
+    };
+
+    let datum = db.fn_def_datum(module.krate, func.to_chalk(&db));
+    let inputs_and_output = datum.binders.skip_binders().inputs_and_output.skip_binders();
+    assert_eq!(inputs_and_output.argument_types.len(), 1);
+    let self_ty: Ty = from_chalk(&db, inputs_and_output.argument_types[0].clone());
+    // `Self` is the first parameter of the method's generics, and the
+    // argument types are shifted in by one binder
+    let expected_self = Ty::Bound(crate::BoundVar::new(DebruijnIndex::ONE, 0));
+    match self_ty {
+        Ty::Apply(crate::ApplicationTy {
+            ctor: TypeCtor::Adt(hir_def::AdtId::StructId(s)),
+            parameters,
+        }) => {
+            assert_eq!(db.struct_data(s).name.to_string(), "Box");
+            assert_eq!(parameters.as_single(), &expected_self);
+        }
+        _ => panic!("unexpected self type {:?}", self_ty),
+    }
+}
+
+#[test]
+fn environment_clause_order_is_ignored() {
+    let (db, module) = single_file_module("fn f() {}");
+    let krate = module.krate;
+    let clause = |ty: Ty| -> chalk_ir::ProgramClause<Interner> {
+        chalk_ir::DomainGoal::FromEnv(chalk_ir::FromEnv::Ty(ty.to_chalk(&db))).cast(&Interner)
+    };
+    let a = clause(Ty::simple(TypeCtor::Bool));
+    let b = clause(Ty::simple(TypeCtor::Char));
+    let env1 =
+        chalk_ir::Environment::new(&Interner).add_clauses(&Interner, vec![a.clone(), b.clone()]);
+    let env2 = chalk_ir::Environment::new(&Interner).add_clauses(&Interner, vec![b, a]);
+
+    let executed = db.log_executed(|| {
+        with_chalk_context(&db, krate, |context| {
+            context.program_clauses_for_env(&env1);
+            context.program_clauses_for_env(&env2);
+        })
+    });
+    let count =
+        executed.iter().filter(|it| it.starts_with("program_clauses_for_chalk_env")).count();
+    assert_eq!(count, 1, "{:#?}", executed);
+}
+
+#[test]
+fn impl_datum_with_projection_where_clause() {
+    let (db, module) = single_file_module(
+        r#"
+trait Iterator { type Item; }
+trait Trait {}
+struct S<T>(T);
+impl<T> Trait for S<T> where T: Iterator<Item = u32> {}
+"#,
+    );
+    let krate = module.krate;
+    let impl_id = db.impl_trait_for(krate, "S<u32>", "Trait").unwrap();
+    let datum = db.impl_datum(krate, Impl::ImplDef(impl_id).to_chalk(&db));
+
+    let where_clauses = &datum.binders.skip_binders().where_clauses;
+    assert_eq!(where_clauses.len(), 2);
+    let projection = where_clauses
+        .iter()
+        .find_map(|wc| match from_chalk(&db, wc.clone()) {
+            GenericPredicate::Projection(it) => Some(it),
+            _ => None,
+        })
+        .expect("no projection predicate");
+    let t = Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0));
+    assert_eq!(projection.projection_ty.parameters, Substs::single(t));
+    assert_eq!(projection.ty, Ty::simple(TypeCtor::Int(crate::primitive::IntTy::u32())));
+}
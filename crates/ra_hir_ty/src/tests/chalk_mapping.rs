@@ -0,0 +1,226 @@
+use chalk_ir::cast::Cast;
+use hir_def::{db::DefDatabase, expr::ExprId, type_ref::Mutability, DefWithBodyId, FunctionId};
+use hir_expand::name::Name;
+use ra_db::fixture::WithFixture;
+use ra_syntax::{AstNode, SourceFile, SyntaxKind};
+
+use crate::{
+    db::HirDatabase,
+    display::HirDisplay,
+    test_db::TestDB,
+    traits::chalk::{from_chalk, mapping::make_binders, Interner, ToChalk},
+    GenericPredicate, Lifetime, ProjectionPredicate, ProjectionTy, Substs, TraitRef, Ty, TypeCtor,
+};
+
+/// Builds up a Chalk substitution one parameter at a time.
+#[derive(Default)]
+struct SubstitutionBuilder {
+    args: Vec<chalk_ir::GenericArg<Interner>>,
+}
+
+impl SubstitutionBuilder {
+    fn push_ty(mut self, ty: chalk_ir::Ty<Interner>) -> Self {
+        self.args.push(ty.cast(&Interner));
+        self
+    }
+
+    fn push_lifetime(mut self, lt: chalk_ir::Lifetime<Interner>) -> Self {
+        self.args.push(lt.cast(&Interner));
+        self
+    }
+
+    fn build(self) -> chalk_ir::Substitution<Interner> {
+        chalk_ir::Substitution::from_iter(&Interner, self.args)
+    }
+
+    fn from_tys(
+        tys: impl IntoIterator<Item = Ty>,
+        db: &dyn HirDatabase,
+    ) -> chalk_ir::Substitution<Interner> {
+        tys.into_iter()
+            .fold(SubstitutionBuilder::default(), |builder, ty| builder.push_ty(ty.to_chalk(db)))
+            .build()
+    }
+}
+
+#[test]
+fn substs_roundtrip() {
+    let db = TestDB::default();
+    let tys = vec![
+        Ty::simple(TypeCtor::Bool),
+        Ty::Bound(crate::BoundVar::new(crate::DebruijnIndex::INNERMOST, 0)),
+    ];
+
+    let chalk = SubstitutionBuilder::from_tys(tys.clone(), &db);
+    assert_eq!(Substs(tys.clone().into()).to_chalk(&db), chalk);
+    let back: Substs = from_chalk(&db, chalk);
+    assert_eq!(back, Substs(tys.into()));
+
+    let with_lifetime = SubstitutionBuilder::default()
+        .push_lifetime(Lifetime::Static.to_chalk(&db))
+        .push_ty(Ty::simple(TypeCtor::Char).to_chalk(&db))
+        .build();
+    assert_eq!(with_lifetime.len(&Interner), 2);
+    assert!(with_lifetime.at(&Interner, 0).lifetime(&Interner).is_some());
+}
+
+#[test]
+fn display_substitution() {
+    let db = TestDB::default();
+    let subst = SubstitutionBuilder::default()
+        .push_lifetime(Lifetime::Static.to_chalk(&db))
+        .push_ty(Ty::simple(TypeCtor::Bool).to_chalk(&db))
+        .push_lifetime(Lifetime::Anonymous.to_chalk(&db))
+        .push_ty(Ty::apply_one(TypeCtor::Slice, Ty::simple(TypeCtor::Char)).to_chalk(&db))
+        .build();
+    assert_eq!(subst.display(&db).to_string(), "'static, bool, '_, [char]");
+    let arg = subst.at(&Interner, 1);
+    assert_eq!(arg.display(&db).to_string(), "bool");
+    let empty = SubstitutionBuilder::default().build();
+    assert_eq!(empty.display(&db).to_string(), "");
+}
+
+fn check_lifetime_roundtrip(db: &TestDB, lifetime: Lifetime) {
+    let chalk = lifetime.clone().to_chalk(db);
+    let back: Lifetime = from_chalk(db, chalk);
+    assert_eq!(back, lifetime);
+}
+
+#[test]
+fn lifetime_roundtrip() {
+    let text = "fn f<'a>(x: &'a u32) {} fn g<'a>(x: &'a u32) {}";
+    let (db, file_id) = TestDB::with_single_file(text);
+    let module = db.module_for_file(file_id);
+    let file = SourceFile::parse(text).tree();
+    let lifetime_token = file
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .find(|it| it.kind() == SyntaxKind::LIFETIME)
+        .unwrap();
+    let named = |func: &str| {
+        let func: FunctionId = db.item_named(module, func);
+        Lifetime::Named(func.into(), Name::new_lifetime(&lifetime_token))
+    };
+
+    check_lifetime_roundtrip(&db, Lifetime::Static);
+    check_lifetime_roundtrip(&db, Lifetime::Anonymous);
+    check_lifetime_roundtrip(&db, named("f"));
+    check_lifetime_roundtrip(&db, named("g"));
+    // the `'a` of `f` and `g` aren't the same lifetime
+    assert_ne!(named("f").to_chalk(&db), named("g").to_chalk(&db));
+    check_lifetime_roundtrip(
+        &db,
+        Lifetime::Bound(crate::BoundVar::new(crate::DebruijnIndex::INNERMOST, 0)),
+    );
+}
+
+#[test]
+fn bound_lifetime_to_chalk() {
+    let db = TestDB::default();
+    let bound = crate::BoundVar::new(crate::DebruijnIndex::INNERMOST, 1);
+    let chalk = Lifetime::Bound(bound).to_chalk(&db);
+    assert_eq!(chalk.data(&Interner), &chalk_ir::LifetimeData::BoundVar(bound));
+
+    // we don't track inference variables for lifetimes
+    let var = chalk_ir::InferenceVar::from(0).to_lifetime(&Interner);
+    let back: Lifetime = from_chalk(&db, var);
+    assert_eq!(back, Lifetime::Anonymous);
+}
+
+#[test]
+fn closure_id_roundtrip() {
+    let (db, file_id) = TestDB::with_single_file("fn f() { let c = || 1; }");
+    let module = db.module_for_file(file_id);
+    let func: FunctionId = db.item_named(module, "f");
+    let def: DefWithBodyId = func.into();
+    let body = db.body(def);
+    let expr = body
+        .exprs
+        .iter()
+        .find_map(|(id, expr)| match expr {
+            hir_def::expr::Expr::Lambda { .. } => Some(id),
+            _ => None,
+        })
+        .unwrap();
+
+    let chalk = (def, expr).to_chalk(&db);
+    let back: (DefWithBodyId, ExprId) = from_chalk(&db, chalk);
+    assert_eq!(back, (def, expr));
+}
+
+#[test]
+fn gat_projection_roundtrip() {
+    let (db, file_id) = TestDB::with_single_file("trait Trait<U> { type Assoc<T>; }");
+    let module = db.module_for_file(file_id);
+    let trait_ = db.item_named(module, "Trait");
+    let assoc_ty = db.trait_data(trait_).associated_types().next().unwrap();
+    // (Self, U, T) in our order
+    let projection = ProjectionTy {
+        associated_ty: assoc_ty,
+        parameters: Substs(
+            vec![Ty::simple(TypeCtor::Bool), Ty::simple(TypeCtor::Char), Ty::simple(TypeCtor::Str)]
+                .into(),
+        ),
+    };
+
+    let chalk = projection.clone().to_chalk(&db);
+    let expected = SubstitutionBuilder::from_tys(
+        vec![Ty::simple(TypeCtor::Str), Ty::simple(TypeCtor::Bool), Ty::simple(TypeCtor::Char)],
+        &db,
+    );
+    assert_eq!(chalk.substitution, expected);
+    let back: ProjectionTy = from_chalk(&db, chalk);
+    assert_eq!(back, projection);
+}
+
+#[test]
+fn generic_predicate_roundtrip() {
+    let (db, file_id) = TestDB::with_single_file("trait Trait<T> { type Assoc; } struct S;");
+    let module = db.module_for_file(file_id);
+    let trait_ = db.item_named(module, "Trait");
+    let s = db.item_named(module, "S");
+    let assoc_ty = db.trait_data(trait_).associated_types().next().unwrap();
+    let tys = vec![
+        Ty::simple(TypeCtor::Bool),
+        Ty::simple(TypeCtor::Adt(s)),
+        Ty::apply_one(TypeCtor::Ref(Mutability::Shared), Ty::simple(TypeCtor::Adt(s))),
+        Ty::Bound(crate::BoundVar::new(crate::DebruijnIndex::INNERMOST, 0)),
+    ];
+
+    for self_ty in &tys {
+        for arg in &tys {
+            let substs = Substs(vec![self_ty.clone(), arg.clone()].into());
+            let mut preds =
+                vec![GenericPredicate::Implemented(TraitRef { trait_, substs: substs.clone() })];
+            for ty in &tys {
+                preds.push(GenericPredicate::Projection(ProjectionPredicate {
+                    projection_ty: ProjectionTy {
+                        associated_ty: assoc_ty,
+                        parameters: substs.clone(),
+                    },
+                    ty: ty.clone(),
+                }));
+            }
+            for pred in preds {
+                let back: GenericPredicate = from_chalk(&db, pred.clone().to_chalk(&db));
+                assert_eq!(back, pred);
+            }
+        }
+    }
+
+    // shapes we can't represent come back as errors instead of panicking
+    let chalk_ty = Ty::simple(TypeCtor::Bool).to_chalk(&db);
+    let lifetime = Lifetime::Static.to_chalk(&db);
+    let unrepresentable = vec![
+        chalk_ir::WhereClause::LifetimeOutlives(chalk_ir::LifetimeOutlives {
+            a: lifetime.clone(),
+            b: lifetime.clone(),
+        }),
+        chalk_ir::WhereClause::TypeOutlives(chalk_ir::TypeOutlives { ty: chalk_ty, lifetime }),
+    ];
+    for where_clause in unrepresentable {
+        let back: GenericPredicate = from_chalk(&db, make_binders(where_clause, 0));
+        assert_eq!(back, GenericPredicate::Error);
+    }
+}
@@ -1051,3 +1051,23 @@ fn test(d: &dyn Trait) {
     "###
     );
 }
+
+#[test]
+fn method_on_raw_pointer() {
+    check_types(
+        r#"
+#[lang = "const_ptr"]
+impl<T> *const T {
+    fn is_null(self) -> bool { false }
+}
+trait Trait { fn get(self) -> u64; }
+impl Trait for *const i32 {}
+fn test(p: *const i32) {
+    p.is_null();
+  //^^^^^^^^^^^ bool
+    p.get();
+  //^^^^^^^ u64
+}
+"#,
+    );
+}
@@ -1578,6 +1578,99 @@ fn test<T: Trait1, U: Trait2>(x: T, y: U) {
     );
 }
 
+#[test]
+fn super_trait_with_params_method_resolution() {
+    check_types(
+        r#"
+mod foo {
+    pub trait SuperTrait<T> {
+        fn foo(&self) -> T;
+    }
+}
+trait Trait: foo::SuperTrait<u32> {}
+fn test<T: Trait>(x: T) {
+    x.foo();
+  //^^^^^^^ u32
+}
+        "#,
+    );
+}
+
+#[test]
+fn marker_trait_overlapping_blanket_impls() {
+    check_types(
+        r#"
+trait Copy {}
+trait Clone {}
+#[marker]
+trait Marker {}
+impl<T: Copy> Marker for T {}
+impl<T: Clone> Marker for T {}
+trait Get { type Output; }
+impl<T: Marker> Get for T { type Output = u32; }
+struct S;
+impl Copy for S {}
+impl Clone for S {}
+fn get<T: Get>(t: T) -> T::Output {}
+fn test() {
+    get(S);
+  //^^^^^^ u32
+}
+        "#,
+    );
+}
+
+#[test]
+fn auto_trait_through_return_position_impl_trait() {
+    check_types(
+        r#"
+auto trait Send {}
+trait Trait {}
+struct S;
+impl Trait for S {}
+struct NotSend;
+impl Trait for NotSend {}
+impl !Send for NotSend {}
+trait Get { type Output; }
+impl<T: Send> Get for T { type Output = u32; }
+fn get<T: Get>(t: T) -> T::Output {}
+fn send() -> impl Trait { S }
+fn not_send() -> impl Trait { NotSend }
+fn test() {
+    get(send());
+  //^^^^^^^^^^^ u32
+    get(not_send());
+  //^^^^^^^^^^^^^^^ {unknown}
+}
+        "#,
+    );
+}
+
+#[test]
+fn assoc_type_impl_trait_value() {
+    check_types(
+        r#"
+trait Trait { fn foo(&self) -> u32; }
+struct S;
+impl Trait for S {}
+trait Get {
+    type Output;
+    fn get(&self) -> Self::Output;
+}
+impl Get for S {
+    type Output = impl Trait;
+    fn get(&self) -> Self::Output { S }
+}
+fn test() {
+    let x = S.get();
+      //^ impl Trait
+    x.foo();
+  //^^^^^^^ u32
+}
+        "#,
+    );
+}
+
 #[test]
 fn super_trait_impl_trait_method_resolution() {
     assert_snapshot!(
@@ -1659,6 +1752,24 @@ fn test() {
     );
 }
 
+#[test]
+fn super_trait_assoc_type_through_sub_trait() {
+    check_types(
+        r#"
+trait SuperTrait { type Item; }
+trait Trait: SuperTrait {}
+struct S;
+impl SuperTrait for S { type Item = u32; }
+impl Trait for S {}
+fn get<T: Trait>(t: T) -> T::Item { loop {} }
+fn test() {
+    get(S);
+  //^^^^^^ u32
+}
+        "#,
+    );
+}
+
 #[test]
 fn fn_trait() {
     assert_snapshot!(
@@ -3061,6 +3172,28 @@ fn foo() {
     );
 }
 
+#[test]
+fn recursive_return_position_impl_trait() {
+    check_types(
+        r#"
+trait Trait {
+    fn foo(&self) -> u32;
+}
+impl Trait for u32 {
+    fn foo(&self) -> u32 { *self }
+}
+
+fn f(n: u32) -> impl Trait {
+    if n == 0 {
+        return 0u32;
+    }
+    f(n - 1).foo();
+  //^^^^^^^^^^^^^^ u32
+    f(n - 1)
+}"#,
+    );
+}
+
 #[test]
 fn variable_kinds_1() {
     check_types(
@@ -3095,3 +3228,222 @@ fn test() {
         "#,
     );
 }
+
+#[test]
+fn auto_trait_zero_variant_enum() {
+    check_types(
+        r#"
+auto trait Send {}
+trait Trait { fn get(&self) -> u32; }
+impl<T: Send> Trait for T {}
+enum Never {}
+fn test(n: Never) {
+    n.get();
+  //^^^^^^^ u32
+}
+        "#,
+    );
+}
+
+#[test]
+fn coerce_unsized_where_clause() {
+    check_types(
+        r#"
+#[lang = "coerce_unsized"]
+pub trait CoerceUnsized<T> {}
+struct Box<T>;
+impl<T: CoerceUnsized<U>, U> CoerceUnsized<Box<U>> for Box<T> {}
+struct A;
+struct B;
+impl CoerceUnsized<B> for A {}
+fn coerce<T: CoerceUnsized<U>, U>(t: T) -> U {}
+fn test(b: Box<A>) {
+    coerce(b);
+  //^^^^^^^^^ Box<B>
+}
+        "#,
+    );
+}
+
+#[test]
+fn fn_ptr_in_chalk_solution() {
+    check_types(
+        r#"
+trait Convert<T> { fn convert(self) -> T; }
+struct S;
+impl Convert<fn(i32, bool) -> str> for S {}
+fn test() {
+    S.convert();
+  //^^^^^^^^^^^ fn(i32, bool) -> str
+}
+        "#,
+    );
+}
+
+#[test]
+fn auto_trait_union_fields() {
+    check_types(
+        r#"
+auto trait Send {}
+trait Trait { fn get(&self) -> u32; }
+impl<T: Send> Trait for T {}
+struct NotSend;
+impl !Send for NotSend {}
+union U1 { a: u32, b: u64 }
+union U2 { a: u32, b: NotSend }
+fn test(u1: U1, u2: U2) {
+    u1.get();
+  //^^^^^^^^ u32
+    u2.get();
+  //^^^^^^^^ {unknown}
+}
+        "#,
+    );
+}
+
+#[test]
+fn auto_trait_struct_and_enum_fields() {
+    check_types(
+        r#"
+auto trait Send {}
+trait Trait { fn get(&self) -> u32; }
+impl<T: Send> Trait for T {}
+struct NotSend;
+impl !Send for NotSend {}
+struct Rc<T>(*const T);
+impl<T> !Send for Rc<T> {}
+struct Wrapper<T>(T);
+enum E1<T> { A(u32), B(Rc<T>) }
+enum E2 { A(u32), B(Wrapper<u64>) }
+union U { a: u32, b: Wrapper<u32> }
+fn test(w1: Wrapper<u32>, w2: Wrapper<*const NotSend>, e1: E1<u32>, e2: E2, u: U) {
+    w1.get();
+  //^^^^^^^^ u32
+    w2.get();
+  //^^^^^^^^ {unknown}
+    e1.get();
+  //^^^^^^^^ {unknown}
+    e2.get();
+  //^^^^^^^^ u32
+    u.get();
+  //^^^^^^^ u32
+}
+        "#,
+    );
+}
+
+#[test]
+fn auto_trait_primitive_types() {
+    check_types(
+        r#"
+auto trait Send {}
+impl<T: ?Sized> !Send for *mut T {}
+trait Trait { fn get(&self) -> u32; }
+impl<T: Send> Trait for T {}
+fn test(a: i32, b: f64, c: &u32, d: *const u8, e: *mut u8) {
+    a.get();
+  //^^^^^^^ u32
+    b.get();
+  //^^^^^^^ u32
+    c.get();
+  //^^^^^^^ u32
+    d.get();
+  //^^^^^^^ u32
+    e.get();
+  //^^^^^^^ {unknown}
+}
+        "#,
+    );
+}
+
+#[test]
+fn coerce_unsized_array_to_slice() {
+    check_types(
+        r#"
+#[lang = "unsize"]
+pub trait Unsize<T: ?Sized> {}
+#[lang = "coerce_unsized"]
+pub trait CoerceUnsized<T> {}
+impl<'a, T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<&'a U> for &'a T {}
+
+trait AsBytes { fn as_bytes(self) -> u32; }
+impl<T: CoerceUnsized<&'static [u8]>> AsBytes for T {}
+fn test(a: &[u8; 4], b: &[u16; 4]) {
+    a.as_bytes();
+  //^^^^^^^^^^^^ u32
+    b.as_bytes();
+  //^^^^^^^^^^^^ {unknown}
+}
+        "#,
+    );
+}
+
+#[test]
+fn assoc_type_default_not_overridden() {
+    check_types(
+        r#"
+trait Trait {
+    type Item = i32;
+    fn get(&self) -> u32;
+}
+struct S;
+impl Trait for S {}
+fn test(s: S) {
+    s.get();
+  //^^^^^^^ u32
+}
+        "#,
+    );
+}
+
+#[test]
+fn auto_trait_explicit_impl() {
+    check_types(
+        r#"
+auto trait Send {}
+trait Trait { fn get(&self) -> u32; }
+impl<T: Send> Trait for T {}
+struct S1;
+struct S2;
+impl !Send for S2 {}
+fn test(s1: S1, s2: S2) {
+    s1.get();
+  //^^^^^^^^ u32
+    s2.get();
+  //^^^^^^^^ {unknown}
+}
+        "#,
+    );
+}
+
+#[test]
+fn implied_bounds_from_param_types() {
+    check_types(
+        r#"
+trait Clone { fn clone(&self) -> Self; }
+struct S<T: Clone>(T);
+fn test<T>(s: S<T>) {
+    s.0.clone();
+  //^^^^^^^^^^^ T
+}
+        "#,
+    );
+}
+
+#[test]
+fn primitive_op_output_projection() {
+    check_types(
+        r#"
+#[lang = "add"]
+pub trait Add<Rhs = Self> { type Output; }
+#[lang = "neg"]
+pub trait Neg { type Output; }
+struct S;
+impl Add<S> for S { type Output = (S, S); }
+
+fn test(a: <i32 as Add<i32>>::Output, b: <f64 as Add>::Output, c: <i8 as Neg>::Output, d: <S as Add<S>>::Output) {
+    (a, b, c, d);
+} //^ (i32, f64, i8, (S, S))
+"#,
+    );
+}
@@ -1,15 +1,24 @@
 //! Trait solving using Chalk.
-use std::sync::Arc;
+use std::{
+    cell::{Cell, RefCell},
+    panic,
+    sync::Arc,
+};
 
 use chalk_ir::cast::Cast;
 use chalk_solve::Solver;
 use hir_def::{
-    expr::ExprId, lang_item::LangItemTarget, DefWithBodyId, ImplId, TraitId, TypeAliasId,
+    expr::ExprId, lang_item::LangItemTarget, AssocContainerId, DefWithBodyId, ImplId, Lookup,
+    TraitId, TypeAliasId,
 };
-use ra_db::{impl_intern_key, salsa, CrateId};
+use hir_expand::name::name;
+use ra_db::{impl_intern_key, salsa, Canceled, CrateId};
 use ra_prof::profile;
 
-use crate::{db::HirDatabase, DebruijnIndex, Substs};
+use crate::{
+    db::HirDatabase, method_resolution::TyFingerprint, primitive::Signedness, ApplicationTy,
+    DebruijnIndex, Substs, TypeCtor,
+};
 
 use super::{Canonical, GenericPredicate, HirDisplay, ProjectionTy, TraitRef, Ty, TypeWalk};
 
@@ -17,6 +26,10 @@ use self::chalk::{from_chalk, Interner, ToChalk};
 
 pub(crate) mod chalk;
 mod builtin;
+mod closure;
+pub(crate) mod object_safety;
+
+pub use object_safety::ObjectSafetyViolation;
 
 // This controls the maximum size of types Chalk considers. If we set this too
 // high, we can run into slow edge cases; if we set it too low, Chalk won't
@@ -24,15 +37,39 @@ mod builtin;
 // FIXME this is currently hardcoded in the recursive solver
 // const CHALK_SOLVER_MAX_SIZE: usize = 10;
 
-/// This controls how much 'time' we give the Chalk solver before giving up.
-const CHALK_SOLVER_FUEL: i32 = 100;
+/// Limits on the work Chalk may do for a single goal; see
+/// `HirDatabase::chalk_solver_config`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChalkSolverConfig {
+    /// This controls how much 'time' we give the Chalk solver before giving
+    /// up, counted in how often it asks whether it should continue. `None`
+    /// means no limit.
+    pub chalk_fuel: Option<u32>,
+}
+
+impl Default for ChalkSolverConfig {
+    fn default() -> Self {
+        ChalkSolverConfig { chalk_fuel: Some(1000) }
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
-struct ChalkContext<'a> {
+pub(crate) struct ChalkContext<'a> {
     db: &'a dyn HirDatabase,
     krate: CrateId,
 }
 
+impl dyn HirDatabase + '_ {
+    /// Runs `f` with our implementation of Chalk's `RustIrDatabase` for `krate`.
+    pub(crate) fn with_chalk_context<R>(
+        &self,
+        krate: CrateId,
+        f: impl FnOnce(&ChalkContext<'_>) -> R,
+    ) -> R {
+        f(&ChalkContext { db: self, krate })
+    }
+}
+
 fn create_chalk_solver() -> chalk_recursive::RecursiveSolver<Interner> {
     let overflow_depth = 100;
     let caching_enabled = true;
@@ -44,7 +81,7 @@ fn create_chalk_solver() -> chalk_recursive::RecursiveSolver<Interner> {
 /// fn foo<T: Default>(t: T) {}
 /// ```
 /// we assume that `T: Default`.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct TraitEnvironment {
     pub predicates: Vec<GenericPredicate>,
 }
@@ -141,46 +178,255 @@ pub(crate) fn trait_solve_query(
         }
     }
 
+    if let Obligation::Trait(trait_ref) = &goal.value.value {
+        if goal.kinds.is_empty() && primitive_impl_exists(db, krate, trait_ref) {
+            let subst = Canonical { value: Substs::empty(), kinds: Arc::new([]) };
+            return Some(Solution::Unique(SolutionVariables(subst)));
+        }
+    }
+
     let canonical = goal.to_chalk(db).cast(&Interner);
 
     // We currently don't deal with universes (I think / hope they're not yet
     // relevant for our use cases?)
     let u_canonical = chalk_ir::UCanonical { canonical, universes: 1 };
-    let solution = solve(db, krate, &u_canonical);
-    solution.map(|solution| solution_from_chalk(db, solution))
+    solve(db, krate, &u_canonical)
 }
 
-fn solve(
+/// Whether `trait_ref` is one of the marker traits that every primitive scalar
+/// type implements, like `i32: Copy`. These goals are very common, and don't
+/// need Chalk to solve them.
+fn primitive_impl_exists(db: &dyn HirDatabase, krate: CrateId, trait_ref: &TraitRef) -> bool {
+    let self_ty = trait_ref.self_ty();
+    let is_scalar = match self_ty {
+        Ty::Apply(a_ty) => matches!(
+            a_ty.ctor,
+            TypeCtor::Bool | TypeCtor::Char | TypeCtor::Int(_) | TypeCtor::Float(_)
+        ),
+        _ => false,
+    };
+    if !is_scalar || trait_ref.substs.len() != 1 {
+        return false;
+    }
+    let trait_ = trait_ref.trait_;
+    let is_lang_trait = |lang_item: &str| {
+        db.lang_item(krate, lang_item.into()) == Some(LangItemTarget::TraitId(trait_))
+    };
+    if is_lang_trait("sized") {
+        // `Sized` is built into Chalk, there are no impls for it
+        return true;
+    }
+    if !is_lang_trait("copy") && !is_lang_trait("clone") {
+        return false;
+    }
+    // `core` implements these for each primitive type; if there is no such
+    // impl (e.g. without `core`), it's up to Chalk
+    let fp = match TyFingerprint::for_impl(self_ty) {
+        Some(it) => it,
+        None => return false,
+    };
+    let in_deps = db.trait_impls_in_deps_for(krate, trait_);
+    let in_crate = db.trait_impls_in_crate(krate);
+    let mut impls =
+        in_deps.for_trait_and_self_ty(trait_, fp).chain(in_crate.for_trait_and_self_ty(trait_, fp));
+    impls.any(|impl_id| {
+        // blanket impls and impls with where clauses need Chalk
+        let impl_self_ty = db.impl_self_ty(impl_id);
+        TyFingerprint::for_impl(&impl_self_ty.value) == Some(fp)
+            && !db.impl_data(impl_id).is_negative
+            && db.generic_predicates(impl_id.into()).is_empty()
+    })
+}
+
+/// The `Output` of the builtin operator impls on primitive types, like
+/// `<i32 as Add<i32>>::Output`, which we know without asking Chalk.
+pub(crate) fn primitive_op_output(
     db: &dyn HirDatabase,
     krate: CrateId,
-    goal: &chalk_ir::UCanonical<chalk_ir::InEnvironment<chalk_ir::Goal<Interner>>>,
-) -> Option<chalk_solve::Solution<Interner>> {
-    let context = ChalkContext { db, krate };
-    log::debug!("solve goal: {:?}", goal);
-    let mut solver = create_chalk_solver();
-
-    let fuel = std::cell::Cell::new(CHALK_SOLVER_FUEL);
-
-    let should_continue = || {
-        context.db.check_canceled();
-        let remaining = fuel.get();
-        fuel.set(remaining - 1);
-        if remaining == 0 {
-            log::debug!("fuel exhausted");
-        }
-        remaining > 0
+    proj_ty: &ProjectionTy,
+) -> Option<Ty> {
+    let trait_ = match proj_ty.associated_ty.lookup(db.upcast()).container {
+        AssocContainerId::TraitId(it) => it,
+        _ => return None,
     };
-    let mut solve = || {
-        let solution = solver.solve_limited(&context, goal, should_continue);
-        log::debug!("solve({:?}) => {:?}", goal, solution);
-        solution
+    if db.type_alias_data(proj_ty.associated_ty).name != name![Output] {
+        return None;
+    }
+    let is_lang_trait = |lang_item: &str| {
+        db.lang_item(krate, lang_item.into()) == Some(LangItemTarget::TraitId(trait_))
     };
-    // don't set the TLS for Chalk unless Chalk debugging is active, to make
-    // extra sure we only use it for debugging
-    let solution =
-        if is_chalk_debug() { chalk::tls::set_current_program(db, solve) } else { solve() };
+    let self_ty = proj_ty.parameters.get(0)?;
+    let self_ctor = match self_ty {
+        Ty::Apply(ApplicationTy { ctor, .. }) => *ctor,
+        _ => return None,
+    };
+    let is_int = matches!(self_ctor, TypeCtor::Int(_));
+    let is_float = matches!(self_ctor, TypeCtor::Float(_));
+    let holds = if is_lang_trait("neg") {
+        is_float || matches!(self_ctor, TypeCtor::Int(it) if it.signedness == Signedness::Signed)
+    } else if is_lang_trait("not") {
+        is_int || self_ctor == TypeCtor::Bool
+    } else if is_lang_trait("shl") || is_lang_trait("shr") {
+        is_int
+            && matches!(
+                proj_ty.parameters.get(1),
+                Some(Ty::Apply(ApplicationTy { ctor: TypeCtor::Int(_), .. }))
+            )
+    } else if ["add", "sub", "mul", "div", "rem"].iter().any(|it| is_lang_trait(it)) {
+        (is_int || is_float) && proj_ty.parameters.get(1) == Some(self_ty)
+    } else if ["bitand", "bitor", "bitxor"].iter().any(|it| is_lang_trait(it)) {
+        (is_int || self_ctor == TypeCtor::Bool) && proj_ty.parameters.get(1) == Some(self_ty)
+    } else {
+        false
+    };
+    if holds {
+        Some(self_ty.clone())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn solve(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    goal: &chalk_ir::UCanonical<chalk_ir::InEnvironment<chalk_ir::Goal<Interner>>>,
+) -> Option<Solution> {
+    let chalk_fuel = db.chalk_solver_config().chalk_fuel;
+    db.with_chalk_context(krate, |context| {
+        log::debug!("solve goal: {:?}", goal);
+        record_chalk_goal(db, goal);
+        let mut solver = create_chalk_solver();
+
+        let fuel_consumed = Cell::new(0);
+        let fuel_exhausted = Cell::new(false);
+
+        let should_continue = || {
+            context.db.check_canceled();
+            if chalk_fuel.map_or(false, |fuel| fuel_consumed.get() >= fuel) {
+                log::debug!("fuel exhausted");
+                fuel_exhausted.set(true);
+                return false;
+            }
+            fuel_consumed.set(fuel_consumed.get() + 1);
+            true
+        };
+        let mut solve = || {
+            let solution = solver.solve_limited(context, goal, should_continue);
+            log::debug!("solve({:?}) => {:?}", goal, solution);
+            record_solve_stats(SolveStats {
+                fuel_consumed: fuel_consumed.get(),
+                fuel_exhausted: fuel_exhausted.get(),
+                panicked: false,
+            });
+            // Chalk reports giving up as an ambiguous solution without any
+            // guidance, which we don't want to confuse with a real one.
+            if fuel_exhausted.get() {
+                return Some(Solution::Overflow);
+            }
+            solution.map(|solution| solution_from_chalk(db, solution))
+        };
+        // A bug in our Chalk integration shouldn't take down everything else,
+        // so treat a panic in the solver as "no solution".
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            // don't set the TLS for Chalk unless Chalk debugging is active, to make
+            // extra sure we only use it for debugging
+            if is_chalk_debug() {
+                let (solution, sources) =
+                    chalk::with_clause_sources(|| chalk::tls::set_current_program(db, solve));
+                for (clause, source) in sources {
+                    log::debug!("consulted {} from {:?}", clause, source);
+                }
+                solution
+            } else {
+                solve()
+            }
+        }));
+        match result {
+            Ok(solution) => solution,
+            Err(err) => {
+                // cancellation is implemented via unwinding, so let it through
+                if err.is::<Canceled>() {
+                    panic::resume_unwind(err);
+                }
+                let msg = err
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| err.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("<unknown>");
+                // Salsa reports cycles between queries without recovery by
+                // panicking; those need to reach the query that can recover.
+                if msg.contains("cycle detected") {
+                    panic::resume_unwind(err);
+                }
+                log::error!("Chalk panicked while solving {:?}: {}", goal, msg);
+                record_solve_stats(SolveStats {
+                    fuel_consumed: fuel_consumed.get(),
+                    fuel_exhausted: fuel_exhausted.get(),
+                    panicked: true,
+                });
+                None
+            }
+        }
+    })
+}
+
+/// How much work Chalk did for a single goal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SolveStats {
+    /// How often the solver asked whether it should continue.
+    pub(crate) fuel_consumed: u32,
+    /// Whether the solver ran out of fuel and gave up.
+    pub(crate) fuel_exhausted: bool,
+    /// Whether the solver panicked, in which case we treat the goal as
+    /// having no solution.
+    pub(crate) panicked: bool,
+}
 
-    solution
+thread_local! {
+    /// Only collected while someone is interested, see `log_solve_stats`.
+    static SOLVE_STATS: RefCell<Option<Vec<SolveStats>>> = RefCell::new(None);
+    /// Only collected while someone is interested, see `log_chalk_goals`.
+    static CHALK_GOALS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+}
+
+fn record_solve_stats(stats: SolveStats) {
+    SOLVE_STATS.with(|log| {
+        if let Some(log) = &mut *log.borrow_mut() {
+            log.push(stats);
+        }
+    })
+}
+
+fn record_chalk_goal(
+    db: &dyn HirDatabase,
+    goal: &chalk_ir::UCanonical<chalk_ir::InEnvironment<chalk_ir::Goal<Interner>>>,
+) {
+    CHALK_GOALS.with(|log| {
+        if let Some(log) = &mut *log.borrow_mut() {
+            // with the program set, the goal is printed with the names of our items
+            let goal =
+                chalk::tls::set_current_program(db, || format!("{:?}", goal.canonical.value.goal));
+            log.push(goal);
+        }
+    })
+}
+
+/// Collects the goals Chalk solves on this thread during `f`, in Chalk's
+/// debug format. Like for `log_solve_stats`, memoized goals don't show up.
+#[cfg(test)]
+pub(crate) fn log_chalk_goals(f: impl FnOnce()) -> Vec<String> {
+    CHALK_GOALS.with(|log| *log.borrow_mut() = Some(Vec::new()));
+    f();
+    CHALK_GOALS.with(|log| log.borrow_mut().take().unwrap_or_default())
+}
+
+/// Collects the stats of all goals Chalk solves on this thread during `f`.
+/// Goals whose solution is already memoized don't show up here.
+#[cfg(test)]
+pub(crate) fn log_solve_stats(f: impl FnOnce()) -> Vec<SolveStats> {
+    SOLVE_STATS.with(|log| *log.borrow_mut() = Some(Vec::new()));
+    f();
+    SOLVE_STATS.with(|log| log.borrow_mut().take().unwrap_or_default())
 }
 
 fn is_chalk_debug() -> bool {
@@ -230,6 +476,10 @@ pub enum Solution {
     /// constraints, since we have not "committed" to any particular solution
     /// yet.
     Ambig(Guidance),
+
+    /// The solver ran out of fuel before it could decide whether the goal
+    /// holds; see `ChalkSolverConfig`.
+    Overflow,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -312,6 +562,9 @@ pub enum AssocTyValue {
     TypeAlias(TypeAliasId),
     /// The output type of the Fn trait implementation.
     ClosureFnTraitImplOutput(ClosureFnTraitImplData),
+    /// An assoc type value like `type Assoc = impl Trait;` from an impl block,
+    /// whose value is the opaque type.
+    OpaqueTy(crate::OpaqueTyId),
 }
 /// This exists just for Chalk, because it needs a unique ID for each associated
 /// type value in an impl (even synthetic ones).
@@ -1,12 +1,13 @@
 //! This module provides the built-in trait implementations, e.g. to make
 //! closures implement `Fn`.
-use hir_def::{expr::Expr, TraitId, TypeAliasId};
+use hir_def::{expr::Expr, AssocContainerId, Lookup, TraitId, TypeAliasId};
 use hir_expand::name::name;
 use ra_db::CrateId;
 
 use super::{AssocTyValue, Impl};
 use crate::{
-    db::HirDatabase, ApplicationTy, BoundVar, DebruijnIndex, Substs, TraitRef, Ty, TypeCtor,
+    db::HirDatabase, utils::generics, ApplicationTy, BoundVar, DebruijnIndex, OpaqueTy, OpaqueTyId,
+    Substs, TraitRef, Ty, TypeCtor,
 };
 
 pub(super) struct BuiltinImplData {
@@ -23,6 +24,28 @@ pub(super) struct BuiltinImplAssocTyValueData {
     pub value: Ty,
 }
 
+/// A source of builtin impls, usually for a certain kind of type.
+trait BuiltinImplProvider {
+    /// Whether this provider can provide impls of the given trait at all.
+    fn provides_impls_for_trait(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        trait_: TraitId,
+    ) -> bool;
+
+    fn provide_impls(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        trait_: TraitId,
+        ty: &Ty,
+        callback: &mut dyn FnMut(Impl),
+    );
+}
+
+const BUILTIN_IMPL_PROVIDERS: &[&dyn BuiltinImplProvider] = &[&ClosureImplProvider];
+
 pub(super) fn get_builtin_impls(
     db: &dyn HirDatabase,
     krate: CrateId,
@@ -32,19 +55,9 @@ pub(super) fn get_builtin_impls(
     trait_: TraitId,
     mut callback: impl FnMut(Impl),
 ) {
-    // Note: since impl_datum needs to be infallible, we need to make sure here
-    // that we have all prerequisites to build the respective impls.
-    if let Ty::Apply(ApplicationTy { ctor: TypeCtor::Closure { def, expr }, .. }) = ty {
-        for &fn_trait in [super::FnTrait::FnOnce, super::FnTrait::FnMut, super::FnTrait::Fn].iter()
-        {
-            if let Some(actual_trait) = fn_trait.get_id(db, krate) {
-                if trait_ == actual_trait {
-                    let impl_ = super::ClosureFnTraitImplData { def: *def, expr: *expr, fn_trait };
-                    if check_closure_fn_trait_impl_prerequisites(db, krate, impl_) {
-                        callback(Impl::ClosureFnTraitImpl(impl_));
-                    }
-                }
-            }
+    for provider in BUILTIN_IMPL_PROVIDERS {
+        if provider.provides_impls_for_trait(db, krate, trait_) {
+            provider.provide_impls(db, krate, trait_, ty, &mut callback);
         }
     }
 }
@@ -66,11 +79,84 @@ pub(super) fn associated_ty_value(
         AssocTyValue::ClosureFnTraitImplOutput(data) => {
             closure_fn_trait_output_assoc_ty_value(db, krate, data)
         }
+        AssocTyValue::OpaqueTy(opaque_ty_id) => opaque_assoc_ty_value(db, opaque_ty_id),
+    }
+}
+
+// Opaque assoc type values
+
+fn opaque_assoc_ty_value(
+    db: &dyn HirDatabase,
+    opaque_ty_id: OpaqueTyId,
+) -> BuiltinImplAssocTyValueData {
+    let type_alias = match opaque_ty_id {
+        OpaqueTyId::AssocTyImplTrait(it) => it,
+        OpaqueTyId::ReturnTypeImplTrait(..) => panic!("not an assoc type value"),
+    };
+    let impl_id = match type_alias.lookup(db.upcast()).container {
+        AssocContainerId::ImplId(it) => it,
+        _ => panic!("assoc ty value should be in impl"),
+    };
+    let trait_ = db.impl_trait(impl_id).expect("assoc ty value should not exist").value.trait_;
+    let assoc_ty_id = db
+        .trait_data(trait_)
+        .associated_type_by_name(&db.type_alias_data(type_alias).name)
+        .expect("assoc ty value should not exist");
+
+    let generics = generics(db.upcast(), type_alias.into());
+    let parameters = super::chalk::assoc_ty_bound_vars(&generics);
+    BuiltinImplAssocTyValueData {
+        impl_: Impl::ImplDef(impl_id),
+        assoc_ty_id,
+        num_vars: generics.len(),
+        value: Ty::Opaque(OpaqueTy { opaque_ty_id, parameters }),
     }
 }
 
 // Closure Fn trait impls
 
+struct ClosureImplProvider;
+
+impl BuiltinImplProvider for ClosureImplProvider {
+    fn provides_impls_for_trait(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        trait_: TraitId,
+    ) -> bool {
+        [super::FnTrait::FnOnce, super::FnTrait::FnMut, super::FnTrait::Fn]
+            .iter()
+            .any(|fn_trait| fn_trait.get_id(db, krate) == Some(trait_))
+    }
+
+    fn provide_impls(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        trait_: TraitId,
+        ty: &Ty,
+        callback: &mut dyn FnMut(Impl),
+    ) {
+        // Note: since impl_datum needs to be infallible, we need to make sure here
+        // that we have all prerequisites to build the respective impls.
+        if let Ty::Apply(ApplicationTy { ctor: TypeCtor::Closure { def, expr }, .. }) = ty {
+            for &fn_trait in
+                [super::FnTrait::FnOnce, super::FnTrait::FnMut, super::FnTrait::Fn].iter()
+            {
+                if let Some(actual_trait) = fn_trait.get_id(db, krate) {
+                    if trait_ == actual_trait {
+                        let impl_ =
+                            super::ClosureFnTraitImplData { def: *def, expr: *expr, fn_trait };
+                        if check_closure_fn_trait_impl_prerequisites(db, krate, impl_) {
+                            callback(Impl::ClosureFnTraitImpl(impl_));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 fn check_closure_fn_trait_impl_prerequisites(
     db: &dyn HirDatabase,
     krate: CrateId,
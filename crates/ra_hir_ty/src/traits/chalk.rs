@@ -1,24 +1,34 @@
 //! Conversion code from/to Chalk.
-use std::sync::Arc;
+use std::{
+    cell::RefCell,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 
 use log::debug;
+use rustc_hash::{FxHashMap, FxHashSet, FxHasher};
 
-use chalk_ir::{fold::shift::Shift, CanonicalVarKinds, GenericArg, TypeName};
+use chalk_ir::{cast::Cast, fold::shift::Shift, CanonicalVarKinds, GenericArg, TypeName};
 use chalk_solve::rust_ir::{self, OpaqueTyDatumBound, WellKnownTrait};
 
 use hir_def::{
+    body::Body,
+    expr::{Expr, ExprId},
     lang_item::{lang_attr, LangItemTarget},
-    AssocContainerId, AssocItemId, HasModule, Lookup, TypeAliasId,
+    AssocContainerId, AssocItemId, EnumVariantId, HasModule, Lookup, TypeAliasId, VariantId,
 };
 use ra_db::{salsa::InternKey, CrateId};
 
-use super::{builtin, AssocTyValue, ChalkContext, Impl};
+use super::{builtin, closure, AssocTyValue, ChalkContext, FnTrait, Impl};
 use crate::{
     db::HirDatabase,
     display::HirDisplay,
+    infer::is_being_inferred,
     method_resolution::{TyFingerprint, ALL_FLOAT_FPS, ALL_INT_FPS},
-    utils::generics,
-    CallableDef, DebruijnIndex, GenericPredicate, Substs, Ty, TypeCtor,
+    utils::{all_super_traits, generics, Generics},
+    ApplicationTy, BoundVar, CallableDef, DebruijnIndex, FnSig, GenericPredicate, Substs, Ty,
+    TypeCtor, TypeWalk,
 };
 use mapping::{convert_where_clauses, generic_predicate_to_inline_bound, make_binders};
 
@@ -26,21 +36,54 @@ pub use self::interner::*;
 
 pub(super) mod tls;
 mod interner;
-mod mapping;
+pub(crate) mod mapping;
 
-pub(super) trait ToChalk {
+pub(crate) trait ToChalk {
     type Chalk;
     fn to_chalk(self, db: &dyn HirDatabase) -> Self::Chalk;
     fn from_chalk(db: &dyn HirDatabase, chalk: Self::Chalk) -> Self;
 }
 
-pub(super) fn from_chalk<T, ChalkT>(db: &dyn HirDatabase, chalk: ChalkT) -> T
+pub(crate) fn from_chalk<T, ChalkT>(db: &dyn HirDatabase, chalk: ChalkT) -> T
 where
     T: ToChalk<Chalk = ChalkT>,
 {
     T::from_chalk(db, chalk)
 }
 
+impl ChalkContext<'_> {
+    /// By the orphan rules, the current crate can only contain impls of
+    /// `trait_` if the trait or one of the types in the parameters is local.
+    /// We can only rely on that if the parameters are fully known though.
+    fn may_have_local_impls(
+        &self,
+        trait_: hir_def::TraitId,
+        parameters: &[GenericArg<Interner>],
+    ) -> bool {
+        let trait_krate = trait_.lookup(self.db.upcast()).container.module(self.db.upcast()).krate;
+        if trait_krate == self.krate {
+            return true;
+        }
+        let tys: Vec<Ty> = parameters
+            .iter()
+            .filter_map(|p| p.ty(&Interner))
+            .map(|ty| from_chalk(self.db, ty.clone()))
+            .collect();
+        let mut fully_known = true;
+        for ty in &tys {
+            ty.walk(&mut |ty| match ty {
+                Ty::Apply(_) | Ty::Placeholder(_) => {}
+                _ => fully_known = false,
+            });
+        }
+        !fully_known
+            || tys
+                .iter()
+                .flat_map(|ty| ty.collect_tyconstrs())
+                .any(|ctor| ctor.krate(self.db) == Some(self.krate))
+    }
+}
+
 impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
     fn associated_ty_data(&self, id: AssocTypeId) -> Arc<AssociatedTyDatum> {
         self.db.associated_ty_data(id)
@@ -51,10 +94,20 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
     fn adt_datum(&self, struct_id: AdtId) -> Arc<StructDatum> {
         self.db.struct_datum(self.krate, struct_id)
     }
-    fn adt_repr(&self, _struct_id: AdtId) -> rust_ir::AdtRepr {
-        unreachable!()
+    fn adt_repr(&self, struct_id: AdtId) -> rust_ir::AdtRepr {
+        let type_ctor: TypeCtor = from_chalk(self.db, TypeName::Adt(struct_id));
+        let repr = match type_ctor {
+            TypeCtor::Adt(hir_def::AdtId::StructId(it)) => self.db.struct_data(it).repr,
+            TypeCtor::Adt(hir_def::AdtId::UnionId(it)) => self.db.union_data(it).repr,
+            TypeCtor::Adt(hir_def::AdtId::EnumId(it)) => self.db.enum_data(it).repr,
+            // closures don't have a `#[repr]`
+            _ => Default::default(),
+        };
+        rust_ir::AdtRepr { repr_c: repr.c, repr_packed: repr.packed }
     }
     fn impl_datum(&self, impl_id: ImplId) -> Arc<ImplDatum> {
+        let impl_: Impl = from_chalk(self.db, impl_id);
+        record_clause_source(&impl_, ProgramClauseSource::Impl(impl_));
         self.db.impl_datum(self.krate, impl_id)
     }
 
@@ -89,7 +142,8 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         }
 
         let self_ty_fp = TyFingerprint::for_impl(&ty);
-        let fps: &[TyFingerprint] = match binder_kind(&ty, binders) {
+        let self_ty_binder_kind = binder_kind(&ty, binders);
+        let fps: &[TyFingerprint] = match self_ty_binder_kind {
             Some(chalk_ir::TyKind::Integer) => &ALL_INT_FPS,
             Some(chalk_ir::TyKind::Float) => &ALL_FLOAT_FPS,
             _ => self_ty_fp.as_ref().map(std::slice::from_ref).unwrap_or(&[]),
@@ -98,18 +152,29 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         // Note: Since we're using impls_for_trait, only impls where the trait
         // can be resolved should ever reach Chalk. `impl_datum` relies on that
         // and will panic if the trait can't be resolved.
-        let in_deps = self.db.trait_impls_in_deps(self.krate);
-        let in_self = self.db.trait_impls_in_crate(self.krate);
-        let impl_maps = [in_deps, in_self];
+        let in_deps = self.db.trait_impls_in_deps_for(self.krate, trait_);
+        let mut impl_maps = vec![in_deps];
+        if self.may_have_local_impls(trait_, parameters) {
+            impl_maps.push(self.db.trait_impls_in_crate(self.krate));
+        }
 
         let id_to_chalk = |id: hir_def::ImplId| Impl::ImplDef(id).to_chalk(self.db);
 
         let mut result: Vec<_> = if fps.is_empty() {
-            debug!("Unrestricted search for {:?} impls...", trait_);
-            impl_maps
+            let result: Vec<_> = impl_maps
                 .iter()
                 .flat_map(|crate_impl_defs| crate_impl_defs.for_trait(trait_).map(id_to_chalk))
-                .collect()
+                .collect();
+            // These are the lookups that can get expensive, since we can't use
+            // the self type to narrow down the search
+            debug!(
+                "unrestricted search for {:?} impls (self ty {:?}, binder kind {:?}) found {} impls",
+                trait_,
+                ty,
+                self_ty_binder_kind,
+                result.len()
+            );
+            result
         } else {
             impl_maps
                 .iter()
@@ -128,23 +193,51 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
             result.push(i.to_chalk(self.db))
         });
 
-        debug!("impls_for_trait returned {} impls", result.len());
+        debug!(
+            "impls_for_trait {:?} (self ty fingerprint {:?}) returned {} impls",
+            trait_,
+            self_ty_fp,
+            result.len()
+        );
+        record_impls_for_trait(trait_, fps.is_empty(), result.len());
         result
     }
     fn impl_provided_for(&self, auto_trait_id: TraitId, struct_id: AdtId) -> bool {
         debug!("impl_provided_for {:?}, {:?}", auto_trait_id, struct_id);
-        false // FIXME
+        let trait_: hir_def::TraitId = from_chalk(self.db, auto_trait_id);
+        let type_ctor: TypeCtor = from_chalk(self.db, TypeName::Adt(struct_id));
+        let self_ty_fp = TyFingerprint::Apply(type_ctor);
+
+        let in_deps = self.db.trait_impls_in_deps(self.krate);
+        let in_self = self.db.trait_impls_in_crate(self.krate);
+        let impl_maps = [in_deps, in_self];
+
+        // `for_trait_and_self_ty` also returns blanket impls, which don't
+        // count as an explicit impl for the type
+        impl_maps.iter().any(|crate_impl_defs| {
+            crate_impl_defs.for_trait_and_self_ty(trait_, self_ty_fp).any(|impl_id| {
+                TyFingerprint::for_impl(&self.db.impl_self_ty(impl_id).value) == Some(self_ty_fp)
+            })
+        })
     }
     fn associated_ty_value(&self, id: AssociatedTyValueId) -> Arc<AssociatedTyValue> {
         self.db.associated_ty_value(self.krate, id)
     }
 
     fn custom_clauses(&self) -> Vec<chalk_ir::ProgramClause<Interner>> {
-        vec![]
+        let clauses = builtin_unsize_clauses(self.db, self.krate);
+        for clause in &clauses {
+            record_clause_source(clause, ProgramClauseSource::Builtin);
+        }
+        clauses
     }
-    fn local_impls_to_coherence_check(&self, _trait_id: TraitId) -> Vec<ImplId> {
-        // We don't do coherence checking (yet)
-        unimplemented!()
+    fn local_impls_to_coherence_check(&self, trait_id: TraitId) -> Vec<ImplId> {
+        let trait_: hir_def::TraitId = from_chalk(self.db, trait_id);
+        self.db
+            .trait_impls_in_crate(self.krate)
+            .for_trait(trait_)
+            .map(|impl_id| Impl::ImplDef(impl_id).to_chalk(self.db))
+            .collect()
     }
     fn interner(&self) -> &Interner {
         &Interner
@@ -165,37 +258,21 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         &self,
         environment: &chalk_ir::Environment<Interner>,
     ) -> chalk_ir::ProgramClauses<Interner> {
-        self.db.program_clauses_for_chalk_env(self.krate, environment.clone())
+        let clauses =
+            self.db.program_clauses_for_chalk_env(self.krate, normalize_environment(environment));
+        for clause in clauses.iter(&Interner) {
+            record_clause_source(clause, ProgramClauseSource::WhereClause);
+        }
+        clauses
     }
 
     fn opaque_ty_data(&self, id: chalk_ir::OpaqueTyId<Interner>) -> Arc<OpaqueTyDatum> {
-        let interned_id = crate::db::InternedOpaqueTyId::from(id);
-        let full_id = self.db.lookup_intern_impl_trait_id(interned_id);
-        let (func, idx) = match full_id {
-            crate::OpaqueTyId::ReturnTypeImplTrait(func, idx) => (func, idx),
-        };
-        let datas =
-            self.db.return_type_impl_traits(func).expect("impl trait id without impl traits");
-        let data = &datas.value.impl_traits[idx as usize];
-        let bound = OpaqueTyDatumBound {
-            bounds: make_binders(
-                data.bounds
-                    .value
-                    .iter()
-                    .cloned()
-                    .filter(|b| !b.is_error())
-                    .map(|b| b.to_chalk(self.db))
-                    .collect(),
-                1,
-            ),
-        };
-        let num_vars = datas.num_binders;
-        Arc::new(OpaqueTyDatum { opaque_ty_id: id, bound: make_binders(bound, num_vars) })
+        self.db.opaque_ty_datum(id)
     }
 
-    fn hidden_opaque_type(&self, _id: chalk_ir::OpaqueTyId<Interner>) -> chalk_ir::Ty<Interner> {
-        // FIXME: actually provide the hidden type; it is relevant for auto traits
-        Ty::Unknown.to_chalk(self.db)
+    fn hidden_opaque_type(&self, id: chalk_ir::OpaqueTyId<Interner>) -> chalk_ir::Ty<Interner> {
+        let opaque_ty_id: crate::OpaqueTyId = from_chalk(self.db, id);
+        hidden_opaque_type(self.db, opaque_ty_id).to_chalk(self.db)
     }
 
     fn force_impl_for(
@@ -203,71 +280,199 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         _well_known: rust_ir::WellKnownTrait,
         _ty: &chalk_ir::TyData<Interner>,
     ) -> Option<bool> {
-        // this method is mostly for rustc
+        // this method is mostly for rustc. Note that `Send` and `Sync` aren't
+        // well-known traits in Chalk (yet), so we couldn't force impls of them
+        // here anyway; Chalk derives the auto trait impls of primitive types
+        // structurally, and `*mut T: !Send` needs the negative impl from `core`.
         None
     }
 
-    fn is_object_safe(&self, _trait_id: chalk_ir::TraitId<Interner>) -> bool {
-        // FIXME: implement actual object safety
-        true
+    fn is_object_safe(&self, trait_id: chalk_ir::TraitId<Interner>) -> bool {
+        let trait_: hir_def::TraitId = from_chalk(self.db, trait_id);
+        // a trait is only object safe if its super traits are as well
+        all_super_traits(self.db.upcast(), trait_)
+            .into_iter()
+            .all(|trait_| self.db.object_safety_violations(trait_).is_empty())
     }
 
     fn closure_kind(
         &self,
-        _closure_id: chalk_ir::ClosureId<Interner>,
+        closure_id: chalk_ir::ClosureId<Interner>,
         _substs: &chalk_ir::Substitution<Interner>,
     ) -> rust_ir::ClosureKind {
-        // FIXME: implement closure support
-        unimplemented!()
+        let (def, expr): (hir_def::DefWithBodyId, hir_def::expr::ExprId) =
+            from_chalk(self.db, closure_id);
+        match closure::closure_kind(self.db, def, expr) {
+            FnTrait::Fn => rust_ir::ClosureKind::Fn,
+            FnTrait::FnMut => rust_ir::ClosureKind::FnMut,
+            FnTrait::FnOnce => rust_ir::ClosureKind::FnOnce,
+        }
     }
     fn closure_inputs_and_output(
         &self,
         _closure_id: chalk_ir::ClosureId<Interner>,
-        _substs: &chalk_ir::Substitution<Interner>,
+        substs: &chalk_ir::Substitution<Interner>,
     ) -> chalk_ir::Binders<rust_ir::FnDefInputsAndOutputDatum<Interner>> {
-        // FIXME: implement closure support
-        unimplemented!()
+        // the only parameter of a closure type is its signature, as a fn pointer
+        let sig_ty: Ty =
+            from_chalk(self.db, substs.at(&Interner, 0).assert_ty_ref(&Interner).clone());
+        let sig = match sig_ty {
+            Ty::Apply(ApplicationTy { ctor: TypeCtor::FnPtr { .. }, parameters }) => {
+                FnSig::from_fn_ptr_substs(&parameters)
+            }
+            _ => FnSig::from_params_and_return(Vec::new(), Ty::Unknown),
+        };
+        let io = rust_ir::FnDefInputsAndOutputDatum {
+            argument_types: sig
+                .params()
+                .iter()
+                .map(|ty| ty.clone().to_chalk(self.db).shifted_in(&Interner))
+                .collect(),
+            return_type: sig.ret().clone().to_chalk(self.db).shifted_in(&Interner),
+        };
+        make_binders(io, 0)
     }
     fn closure_upvars(
         &self,
-        _closure_id: chalk_ir::ClosureId<Interner>,
+        closure_id: chalk_ir::ClosureId<Interner>,
         _substs: &chalk_ir::Substitution<Interner>,
     ) -> chalk_ir::Binders<chalk_ir::Ty<Interner>> {
-        // FIXME: implement closure support
-        unimplemented!()
+        let (def, expr): (hir_def::DefWithBodyId, hir_def::expr::ExprId) =
+            from_chalk(self.db, closure_id);
+        // the upvar types come from inference, so they don't mention any bound vars
+        make_binders(closure::closure_upvars_ty(self.db, def, expr).to_chalk(self.db), 0)
     }
     fn closure_fn_substitution(
         &self,
         _closure_id: chalk_ir::ClosureId<Interner>,
         _substs: &chalk_ir::Substitution<Interner>,
     ) -> chalk_ir::Substitution<Interner> {
-        // FIXME: implement closure support
-        unimplemented!()
+        // `closure_inputs_and_output` doesn't bind anything
+        chalk_ir::Substitution::empty(&Interner)
     }
 
-    fn trait_name(&self, _trait_id: chalk_ir::TraitId<Interner>) -> String {
-        unimplemented!()
+    fn trait_name(&self, trait_id: chalk_ir::TraitId<Interner>) -> String {
+        let trait_: hir_def::TraitId = from_chalk(self.db, trait_id);
+        self.db.trait_data(trait_).name.to_string()
     }
-    fn adt_name(&self, _struct_id: chalk_ir::AdtId<Interner>) -> String {
-        unimplemented!()
+    fn adt_name(&self, struct_id: chalk_ir::AdtId<Interner>) -> String {
+        let type_ctor: TypeCtor = from_chalk(self.db, TypeName::Adt(struct_id));
+        match type_ctor {
+            TypeCtor::Adt(hir_def::AdtId::StructId(it)) => self.db.struct_data(it).name.to_string(),
+            TypeCtor::Adt(hir_def::AdtId::UnionId(it)) => self.db.union_data(it).name.to_string(),
+            TypeCtor::Adt(hir_def::AdtId::EnumId(it)) => self.db.enum_data(it).name.to_string(),
+            // closures are passed to Chalk as ADTs as well
+            _ => format!("{:?}", type_ctor),
+        }
     }
-    fn assoc_type_name(&self, _assoc_ty_id: chalk_ir::AssocTypeId<Interner>) -> String {
-        unimplemented!()
+    fn assoc_type_name(&self, assoc_ty_id: chalk_ir::AssocTypeId<Interner>) -> String {
+        let type_alias: TypeAliasId = from_chalk(self.db, assoc_ty_id);
+        self.db.type_alias_data(type_alias).name.to_string()
     }
-    fn opaque_type_name(&self, _opaque_ty_id: chalk_ir::OpaqueTyId<Interner>) -> String {
-        unimplemented!()
+    fn opaque_type_name(&self, opaque_ty_id: chalk_ir::OpaqueTyId<Interner>) -> String {
+        let opaque_ty_id: crate::OpaqueTyId = from_chalk(self.db, opaque_ty_id);
+        match opaque_ty_id {
+            crate::OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
+                format!("{{impl trait {} of {}}}", idx, self.db.function_data(func).name)
+            }
+            crate::OpaqueTyId::AssocTyImplTrait(type_alias) => {
+                format!("{{impl trait of {}}}", self.db.type_alias_data(type_alias).name)
+            }
+        }
     }
-    fn fn_def_name(&self, _fn_def_id: chalk_ir::FnDefId<Interner>) -> String {
-        unimplemented!()
+    fn fn_def_name(&self, fn_def_id: chalk_ir::FnDefId<Interner>) -> String {
+        let callable_def: CallableDef = from_chalk(self.db, fn_def_id);
+        match callable_def {
+            CallableDef::FunctionId(it) => self.db.function_data(it).name.to_string(),
+            CallableDef::StructId(it) => self.db.struct_data(it).name.to_string(),
+            CallableDef::EnumVariantId(it) => {
+                self.db.enum_data(it.parent).variants[it.local_id].name.to_string()
+            }
+        }
     }
 }
 
+/// Statistics about `impls_for_trait` calls, collected per thread while
+/// someone is interested. This is
+/// useful for finding out which traits get looked up the most, and how often
+/// we can't narrow down the search using the self type fingerprint.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TraitQueryMetrics {
+    pub(crate) per_trait: FxHashMap<hir_def::TraitId, ImplsForTraitMetrics>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ImplsForTraitMetrics {
+    pub(crate) calls: usize,
+    /// Calls where we had no fingerprint and had to search all impls.
+    pub(crate) unrestricted_calls: usize,
+    pub(crate) impls_returned: usize,
+}
+
+thread_local! {
+    /// Only collected while someone is interested, see `log_trait_query_metrics`.
+    static TRAIT_QUERY_METRICS: RefCell<Option<TraitQueryMetrics>> = RefCell::new(None);
+}
+
+fn record_impls_for_trait(trait_: hir_def::TraitId, unrestricted: bool, num_impls: usize) {
+    TRAIT_QUERY_METRICS.with(|metrics| {
+        if let Some(metrics) = &mut *metrics.borrow_mut() {
+            let entry = metrics.per_trait.entry(trait_).or_default();
+            entry.calls += 1;
+            if unrestricted {
+                entry.unrestricted_calls += 1;
+            }
+            entry.impls_returned += num_impls;
+        }
+    })
+}
+
+/// Collects the metrics of all `impls_for_trait` calls on this thread during
+/// `f`. Like for `log_solve_stats`, memoized goals don't show up.
+#[cfg(test)]
+pub(crate) fn log_trait_query_metrics(f: impl FnOnce()) -> TraitQueryMetrics {
+    TRAIT_QUERY_METRICS.with(|metrics| *metrics.borrow_mut() = Some(TraitQueryMetrics::default()));
+    f();
+    TRAIT_QUERY_METRICS.with(|metrics| metrics.borrow_mut().take().unwrap_or_default())
+}
+
+/// Environments that only differ in the order of their clauses (or in
+/// duplicate clauses) are the same for Chalk, so we sort the clauses before
+/// using an environment as a query key, to get the same result for both.
+fn normalize_environment(
+    environment: &chalk_ir::Environment<Interner>,
+) -> chalk_ir::Environment<Interner> {
+    let mut clauses: Vec<_> = environment.clauses.iter(&Interner).cloned().collect();
+    clauses.sort_by_key(|clause| clause_hash(clause));
+    clauses.dedup();
+    chalk_ir::Environment::new(&Interner).add_clauses(&Interner, clauses)
+}
+
 pub(crate) fn program_clauses_for_chalk_env_query(
     db: &dyn HirDatabase,
     krate: CrateId,
     environment: chalk_ir::Environment<Interner>,
 ) -> chalk_ir::ProgramClauses<Interner> {
-    chalk_solve::program_clauses_for_env(&ChalkContext { db, krate }, &environment)
+    // Chalk elaborates the environment into a set of clauses, so duplicate
+    // where clauses are already only returned once.
+    db.with_chalk_context(krate, |context| {
+        chalk_solve::program_clauses_for_env(context, &environment)
+    })
+}
+
+/// We put the parameters of the parent (the trait or impl) of an associated
+/// type before the associated type's own parameters, but Chalk puts them
+/// last. This returns bound variables in Chalk's order, to be substituted
+/// for the parameters of something using our order. The orders only differ
+/// for generic associated types.
+pub(super) fn assoc_ty_bound_vars(generics: &Generics) -> Substs {
+    let (_total, parent_len, own_len) = generics.len_split();
+    Substs(
+        (own_len..own_len + parent_len)
+            .chain(0..own_len)
+            .map(|idx| Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, idx)))
+            .collect(),
+    )
 }
 
 pub(crate) fn associated_ty_data_query(
@@ -284,20 +489,29 @@ pub(crate) fn associated_ty_data_query(
     // Lower bounds -- we could/should maybe move this to a separate query in `lower`
     let type_alias_data = db.type_alias_data(type_alias);
     let generic_params = generics(db.upcast(), type_alias.into());
-    let bound_vars = Substs::bound_vars(&generic_params, DebruijnIndex::INNERMOST);
+    // the binders of the datum are in Chalk's order, see `assoc_ty_bound_vars`
+    let bound_vars = assoc_ty_bound_vars(&generic_params);
     let resolver = hir_def::resolver::HasResolver::resolver(type_alias, db.upcast());
     let ctx = crate::TyLoweringContext::new(db, &resolver)
         .with_type_param_mode(crate::lower::TypeParamLoweringMode::Variable);
-    let self_ty = Ty::Bound(crate::BoundVar::new(crate::DebruijnIndex::INNERMOST, 0));
+    let self_ty = Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0));
+    let chalk_self_ty = self_ty.clone().subst_bound_vars(&bound_vars);
+    // FIXME: lifetime bounds like `type Foo: 'static` are lowered to
+    // `TypeBound::Error` and skipped here; the version of Chalk we use has no
+    // `InlineBound` for them either.
     let bounds = type_alias_data
         .bounds
         .iter()
         .flat_map(|bound| GenericPredicate::from_type_bound(&ctx, bound, self_ty.clone()))
-        .filter_map(|pred| generic_predicate_to_inline_bound(db, &pred, &self_ty))
+        .map(|pred| pred.subst_bound_vars(&bound_vars))
+        .filter_map(|pred| generic_predicate_to_inline_bound(db, &pred, &chalk_self_ty))
         .map(|bound| make_binders(bound.shifted_in(&Interner), 0))
         .collect();
 
     let where_clauses = convert_where_clauses(db, type_alias.into(), &bound_vars);
+    // FIXME: Chalk doesn't know about associated type defaults, so a default
+    // (`type Item = i32;` in the trait) is ignored here; projections for impls
+    // that don't specify the type just won't normalize.
     let bound_data = rust_ir::AssociatedTyDatumBound { bounds, where_clauses };
     let datum = AssociatedTyDatum {
         trait_id: trait_.to_chalk(db),
@@ -308,6 +522,133 @@ pub(crate) fn associated_ty_data_query(
     Arc::new(datum)
 }
 
+/// Program clauses for built-in impls that we have to tell Chalk about
+/// ourselves. Currently this is just `forall<T> { [T; N]: Unsize<[T]> }`; the
+/// `CoerceUnsized` impls in libcore build on it.
+fn builtin_unsize_clauses(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+) -> Vec<chalk_ir::ProgramClause<Interner>> {
+    let unsize_trait = match db.lang_item(krate, "unsize".into()) {
+        Some(LangItemTarget::TraitId(trait_)) => trait_,
+        _ => return Vec::new(),
+    };
+    let elem_ty = Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0));
+    let trait_ref = crate::TraitRef {
+        trait_: unsize_trait,
+        substs: Substs::build_for_def(db, unsize_trait)
+            .push(Ty::apply_one(TypeCtor::Array, elem_ty.clone()))
+            .push(Ty::apply_one(TypeCtor::Slice, elem_ty))
+            .build(),
+    };
+    vec![make_binders(trait_ref.to_chalk(db), 1).cast(&Interner)]
+}
+
+pub(crate) fn opaque_ty_datum_query(db: &dyn HirDatabase, id: OpaqueTyId) -> Arc<OpaqueTyDatum> {
+    debug!("opaque_ty_datum {:?}", id);
+    let interned_id = crate::db::InternedOpaqueTyId::from(id);
+    let full_id = db.lookup_intern_impl_trait_id(interned_id);
+    debug_assert_eq!(
+        db.intern_impl_trait_id(full_id),
+        interned_id,
+        "opaque type id {:?} doesn't round-trip",
+        full_id
+    );
+    let bounds = full_id.bounds(db).expect("impl trait id without impl traits");
+    let bound = OpaqueTyDatumBound {
+        bounds: make_binders(
+            bounds
+                .value
+                .value
+                .iter()
+                .cloned()
+                .filter(|b| !b.is_error())
+                .map(|b| b.to_chalk(db))
+                .collect(),
+            1,
+        ),
+    };
+    let num_vars = bounds.num_binders;
+    Arc::new(OpaqueTyDatum { opaque_ty_id: id, bound: make_binders(bound, num_vars) })
+}
+
+/// Finds the concrete type behind a return-position `impl Trait` by matching
+/// up the declared return type with the types of the values the function
+/// returns. Like the bounds of the opaque type, the result refers to the
+/// function's type parameters as bound variables.
+///
+/// While the function itself is being inferred (e.g. because it's recursive),
+/// we don't know the hidden type yet.
+fn hidden_opaque_type(db: &dyn HirDatabase, id: crate::OpaqueTyId) -> Ty {
+    let func = match id {
+        crate::OpaqueTyId::ReturnTypeImplTrait(func, _) => func,
+        // FIXME: this would need to look at the uses in the impl's methods
+        crate::OpaqueTyId::AssocTyImplTrait(_) => return Ty::Unknown,
+    };
+    if is_being_inferred(func.into()) {
+        return Ty::Unknown;
+    }
+    let sig = db.callable_item_signature(func.into());
+    let body = db.body(func.into());
+    let infer = db.infer(func.into());
+    let candidates: Vec<Ty> = returned_exprs(&body)
+        .into_iter()
+        .filter_map(|expr| find_hidden_type(sig.value.ret(), &infer[expr], id))
+        // a recursive call doesn't tell us anything either
+        .filter(|ty| match ty {
+            Ty::Opaque(opaque_ty) => opaque_ty.opaque_ty_id != id,
+            _ => true,
+        })
+        .collect();
+    // diverging returns don't tell us anything, unless all of them diverge
+    let hidden = candidates
+        .iter()
+        .find(|ty| match ty {
+            Ty::Unknown | Ty::Apply(ApplicationTy { ctor: TypeCtor::Never, .. }) => false,
+            _ => true,
+        })
+        .or_else(|| candidates.first())
+        .cloned();
+    let generics = generics(db.upcast(), func.into());
+    hidden.unwrap_or(Ty::Unknown).fold(&mut |ty| match ty {
+        Ty::Placeholder(param) => match generics.param_idx(param) {
+            Some(idx) => Ty::Bound(crate::BoundVar::new(DebruijnIndex::INNERMOST, idx)),
+            None => Ty::Unknown,
+        },
+        ty => ty,
+    })
+}
+
+/// The expressions whose values a body returns: its tail expression and the
+/// operands of the `return`s in it, except for those in closures.
+fn returned_exprs(body: &Body) -> Vec<ExprId> {
+    fn walk(body: &Body, expr: ExprId, acc: &mut Vec<ExprId>) {
+        match &body[expr] {
+            Expr::Lambda { .. } => {}
+            Expr::Return { expr: Some(returned) } => {
+                acc.push(*returned);
+                walk(body, *returned, acc);
+            }
+            e => e.walk_child_exprs(|child| walk(body, child, acc)),
+        }
+    }
+    let mut acc = vec![body.body_expr];
+    walk(body, body.body_expr, &mut acc);
+    acc
+}
+
+fn find_hidden_type(declared: &Ty, actual: &Ty, id: crate::OpaqueTyId) -> Option<Ty> {
+    match (declared, actual) {
+        (Ty::Opaque(opaque_ty), _) if opaque_ty.opaque_ty_id == id => Some(actual.clone()),
+        (Ty::Apply(declared), Ty::Apply(actual)) if declared.ctor == actual.ctor => declared
+            .parameters
+            .iter()
+            .zip(actual.parameters.iter())
+            .find_map(|(declared, actual)| find_hidden_type(declared, actual, id)),
+        _ => None,
+    }
+}
+
 pub(crate) fn trait_datum_query(
     db: &dyn HirDatabase,
     krate: CrateId,
@@ -318,14 +659,14 @@ pub(crate) fn trait_datum_query(
     let trait_data = db.trait_data(trait_);
     debug!("trait {:?} = {:?}", trait_id, trait_data.name);
     let generic_params = generics(db.upcast(), trait_.into());
-    let bound_vars = Substs::bound_vars(&generic_params, DebruijnIndex::INNERMOST);
+    let bound_vars = Substs::for_generics(&generic_params);
     let flags = rust_ir::TraitFlags {
         auto: trait_data.auto,
         upstream: trait_.lookup(db.upcast()).container.module(db.upcast()).krate != krate,
         non_enumerable: true,
-        coinductive: false, // only relevant for Chalk testing
-        // FIXME: set these flags correctly
-        marker: false,
+        coinductive: is_coinductive_trait(db, trait_),
+        marker: db.attrs(trait_.into()).by_key("marker").exists(),
+        // FIXME: set this flag correctly
         fundamental: false,
     };
     let where_clauses = convert_where_clauses(db, trait_.into(), &bound_vars);
@@ -344,6 +685,44 @@ pub(crate) fn trait_datum_query(
     Arc::new(trait_datum)
 }
 
+/// Whether Chalk may assume the trait holds when proving it runs into a
+/// cycle, instead of treating the cycle as an error. Auto traits always work
+/// like this, so Chalk doesn't need this flag for them; for other traits this
+/// is opt-in via `#[rustc_coinductive]`, which no trait in `std` currently
+/// uses.
+pub(crate) fn is_coinductive_trait(db: &dyn HirDatabase, trait_: hir_def::TraitId) -> bool {
+    db.attrs(trait_.into()).by_key("rustc_coinductive").exists()
+}
+
+/// If lowering a trait depends on itself (e.g. through cyclic super traits),
+/// we give Chalk a trait without any where clauses or associated types.
+pub(crate) fn trait_datum_recover(
+    db: &dyn HirDatabase,
+    cycle: &[String],
+    krate: &CrateId,
+    trait_id: &TraitId,
+) -> Arc<TraitDatum> {
+    let trait_: hir_def::TraitId = from_chalk(db, *trait_id);
+    log::warn!("cycle while lowering trait {:?} for Chalk: {:?}", trait_, cycle);
+    let num_params = generics(db.upcast(), trait_.into()).len();
+    let flags = rust_ir::TraitFlags {
+        auto: false,
+        upstream: trait_.lookup(db.upcast()).container.module(db.upcast()).krate != *krate,
+        non_enumerable: true,
+        coinductive: false,
+        marker: false,
+        fundamental: false,
+    };
+    let trait_datum_bound = rust_ir::TraitDatumBound { where_clauses: Vec::new() };
+    Arc::new(TraitDatum {
+        id: *trait_id,
+        binders: make_binders(trait_datum_bound, num_params),
+        flags,
+        associated_ty_ids: Vec::new(),
+        well_known: None,
+    })
+}
+
 fn well_known_trait_from_lang_attr(name: &str) -> Option<WellKnownTrait> {
     Some(match name {
         "sized" => WellKnownTrait::Sized,
@@ -359,6 +738,8 @@ fn well_known_trait_from_lang_attr(name: &str) -> Option<WellKnownTrait> {
 }
 
 fn lang_attr_from_well_known_trait(attr: WellKnownTrait) -> &'static str {
+    // Keep this match free of wildcards, so that new well-known traits in
+    // Chalk fail to compile here instead of silently not being found.
     match attr {
         WellKnownTrait::Sized => "sized",
         WellKnownTrait::Copy => "copy",
@@ -380,29 +761,50 @@ pub(crate) fn struct_datum_query(
     let type_ctor: TypeCtor = from_chalk(db, TypeName::Adt(struct_id));
     debug!("struct {:?} = {:?}", struct_id, type_ctor);
     let num_params = type_ctor.num_ty_params(db);
-    let upstream = type_ctor.krate(db) != Some(krate);
+    let type_ctor_flags = db.type_ctor_flags(krate, type_ctor);
     let where_clauses = type_ctor
         .as_generic_def()
         .map(|generic_def| {
             let generic_params = generics(db.upcast(), generic_def);
-            let bound_vars = Substs::bound_vars(&generic_params, DebruijnIndex::INNERMOST);
+            // lifetime parameters aren't part of `Generics` (and we don't
+            // lower them at all), so the binders only cover type parameters
+            debug_assert_eq!(generic_params.len(), num_params);
+            let bound_vars = Substs::for_generics(&generic_params);
             convert_where_clauses(db, generic_def, &bound_vars)
         })
         .unwrap_or_else(Vec::new);
     let flags = rust_ir::AdtFlags {
-        upstream,
-        // FIXME set fundamental and phantom_data flags correctly
-        fundamental: false,
-        phantom_data: false,
+        upstream: type_ctor_flags.upstream,
+        fundamental: type_ctor_flags.fundamental,
+        phantom_data: type_ctor_flags.phantom_data,
+    };
+    let variant_datum = |variant: VariantId| rust_ir::AdtVariantDatum {
+        fields: db
+            .field_types(variant)
+            .iter()
+            .map(|(_, ty)| ty.value.clone().to_chalk(db))
+            .collect(),
     };
-    // FIXME provide enum variants properly (for auto traits)
-    let variant = rust_ir::AdtVariantDatum {
-        fields: Vec::new(), // FIXME add fields (only relevant for auto traits),
+    let variants = match type_ctor {
+        TypeCtor::Adt(hir_def::AdtId::StructId(s)) => vec![variant_datum(s.into())],
+        TypeCtor::Adt(hir_def::AdtId::UnionId(u)) => vec![variant_datum(u.into())],
+        TypeCtor::Adt(hir_def::AdtId::EnumId(e)) => db
+            .enum_data(e)
+            .variants
+            .iter()
+            .map(|(local_id, _)| variant_datum(EnumVariantId { parent: e, local_id }.into()))
+            .collect(),
+        // closures are passed to Chalk as ADTs too, but don't have any fields
+        _ => vec![rust_ir::AdtVariantDatum { fields: Vec::new() }],
     };
-    let struct_datum_bound = rust_ir::AdtDatumBound { variants: vec![variant], where_clauses };
+    let kind = match type_ctor {
+        TypeCtor::Adt(hir_def::AdtId::EnumId(_)) => rust_ir::AdtKind::Enum,
+        TypeCtor::Adt(hir_def::AdtId::UnionId(_)) => rust_ir::AdtKind::Union,
+        _ => rust_ir::AdtKind::Struct,
+    };
+    let struct_datum_bound = rust_ir::AdtDatumBound { variants, where_clauses };
     let struct_datum = StructDatum {
-        // FIXME set ADT kind
-        kind: rust_ir::AdtKind::Struct,
+        kind,
         id: struct_id,
         binders: make_binders(struct_datum_bound, num_params),
         flags,
@@ -424,6 +826,89 @@ pub(crate) fn impl_datum_query(
     }
 }
 
+/// If lowering an impl depends on itself, we give Chalk a negative impl for
+/// the error type instead, so that it can't contribute to any solution.
+pub(crate) fn impl_datum_recover(
+    db: &dyn HirDatabase,
+    cycle: &[String],
+    krate: &CrateId,
+    impl_id: &ImplId,
+) -> Arc<ImplDatum> {
+    let impl_: Impl = from_chalk(db, *impl_id);
+    log::warn!("cycle while lowering impl {:?} for Chalk: {:?}", impl_, cycle);
+    let trait_ = match impl_ {
+        Impl::ImplDef(it) => db.impl_trait(it).map(|trait_ref| trait_ref.value.trait_),
+        Impl::ClosureFnTraitImpl(data) => data.fn_trait.get_id(db, *krate),
+    }
+    .expect("invalid impl passed to Chalk");
+    let num_params = generics(db.upcast(), trait_.into()).len();
+    let substs = Substs(std::iter::repeat(Ty::Unknown).take(num_params).collect());
+    let trait_ref = crate::TraitRef { trait_, substs }.to_chalk(db);
+    let impl_datum_bound = rust_ir::ImplDatumBound { trait_ref, where_clauses: Vec::new() };
+    Arc::new(ImplDatum {
+        polarity: rust_ir::Polarity::Negative,
+        binders: make_binders(impl_datum_bound, 0),
+        impl_type: rust_ir::ImplType::External,
+        associated_ty_value_ids: Vec::new(),
+    })
+}
+
+/// Where something Chalk consulted while solving a goal came from. This is
+/// only recorded while Chalk debugging is enabled (`CHALK_DEBUG`), to make it
+/// easier to see why Chalk came up with some solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProgramClauseSource {
+    /// A clause from the environment, i.e. an explicit where clause like
+    /// `T: Trait` or something that follows from one.
+    WhereClause,
+    /// An impl, either an impl block or one of our builtin impls.
+    Impl(Impl),
+    /// One of the builtin clauses we give Chalk, like the unsizing rules.
+    Builtin,
+}
+
+/// The sources of the clauses consulted during the current `solve` call,
+/// keyed by the hash of the clause (or impl), so each is only logged once.
+#[derive(Debug, Default)]
+struct ClauseSources {
+    seen: FxHashSet<u64>,
+    used: Vec<(String, ProgramClauseSource)>,
+}
+
+thread_local! {
+    /// Only collected during a `solve` call while Chalk debugging is enabled,
+    /// see `with_clause_sources`.
+    static CLAUSE_SOURCES: RefCell<Option<ClauseSources>> = RefCell::new(None);
+}
+
+fn clause_hash(clause: &impl Hash) -> u64 {
+    let mut hasher = FxHasher::default();
+    clause.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn record_clause_source(clause: &(impl Hash + fmt::Debug), source: ProgramClauseSource) {
+    CLAUSE_SOURCES.with(|sources| {
+        if let Some(sources) = &mut *sources.borrow_mut() {
+            if sources.seen.insert(clause_hash(clause)) {
+                sources.used.push((format!("{:?}", clause), source));
+            }
+        }
+    })
+}
+
+/// Records the sources of the clauses Chalk consults during `f`, starting
+/// from scratch. Solving can nest (e.g. through `hidden_opaque_type`), so the
+/// outer record is restored afterwards.
+pub(super) fn with_clause_sources<T>(
+    f: impl FnOnce() -> T,
+) -> (T, Vec<(String, ProgramClauseSource)>) {
+    let outer = CLAUSE_SOURCES.with(|sources| sources.replace(Some(ClauseSources::default())));
+    let result = f();
+    let sources = CLAUSE_SOURCES.with(|sources| sources.replace(outer));
+    (result, sources.map(|it| it.used).unwrap_or_default())
+}
+
 fn impl_def_datum(
     db: &dyn HirDatabase,
     krate: CrateId,
@@ -432,13 +917,16 @@ fn impl_def_datum(
 ) -> Arc<ImplDatum> {
     let trait_ref = db
         .impl_trait(impl_id)
-        // ImplIds for impls where the trait ref can't be resolved should never reach Chalk
+        // ImplIds for impls where the trait ref can't be resolved should never
+        // reach Chalk. Neither should inherent impls: Chalk has no concept of
+        // them, and we only ever hand it impls from `TraitImpls`. Inherent
+        // methods are looked up via `InherentImpls` in method resolution.
         .expect("invalid impl passed to Chalk")
         .value;
     let impl_data = db.impl_data(impl_id);
 
     let generic_params = generics(db.upcast(), impl_id.into());
-    let bound_vars = Substs::bound_vars(&generic_params, DebruijnIndex::INNERMOST);
+    let bound_vars = Substs::for_generics(&generic_params);
     let trait_ = trait_ref.trait_;
     let impl_type = if impl_id.lookup(db.upcast()).container.module(db.upcast()).krate == krate {
         rust_ir::ImplType::Local
@@ -448,11 +936,11 @@ fn impl_def_datum(
     let where_clauses = convert_where_clauses(db, impl_id.into(), &bound_vars);
     let negative = impl_data.is_negative;
     debug!(
-        "impl {:?}: {}{} where {:?}",
+        "impl {:?}: {}{} where {}",
         chalk_id,
         if negative { "!" } else { "" },
         trait_ref.display(db),
-        where_clauses
+        QuantifiedWhereClauseDisplay { db, clauses: &where_clauses }
     );
     let trait_ref = trait_ref.to_chalk(db);
 
@@ -472,7 +960,11 @@ fn impl_def_datum(
             let name = &db.type_alias_data(type_alias).name;
             trait_data.associated_type_by_name(name).is_some()
         })
-        .map(|type_alias| AssocTyValue::TypeAlias(type_alias).to_chalk(db))
+        .map(|type_alias| match crate::lower::assoc_ty_impl_trait(db, type_alias) {
+            Some(_) => AssocTyValue::OpaqueTy(crate::OpaqueTyId::AssocTyImplTrait(type_alias)),
+            None => AssocTyValue::TypeAlias(type_alias),
+        })
+        .map(|value| value.to_chalk(db))
         .collect();
     debug!("impl_datum: {:?}", impl_datum_bound);
     let impl_datum = ImplDatum {
@@ -484,6 +976,26 @@ fn impl_def_datum(
     Arc::new(impl_datum)
 }
 
+/// Prints Chalk where clauses like we'd write them in Rust, which is a lot
+/// more readable than their `Debug` output.
+struct QuantifiedWhereClauseDisplay<'a> {
+    db: &'a dyn HirDatabase,
+    clauses: &'a [chalk_ir::QuantifiedWhereClause<Interner>],
+}
+
+impl fmt::Display for QuantifiedWhereClauseDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, clause) in self.clauses.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            let pred: GenericPredicate = from_chalk(self.db, clause.clone());
+            write!(f, "{}", pred.display(self.db))?;
+        }
+        Ok(())
+    }
+}
+
 pub(crate) fn associated_ty_value_query(
     db: &dyn HirDatabase,
     krate: CrateId,
@@ -516,7 +1028,12 @@ fn type_alias_associated_ty_value(
         .associated_type_by_name(&type_alias_data.name)
         .expect("assoc ty value should not exist"); // validated when building the impl data as well
     let ty = db.ty(type_alias.into());
-    let value_bound = rust_ir::AssociatedTyValueBound { ty: ty.value.to_chalk(db) };
+    let generic_params = generics(db.upcast(), type_alias.into());
+    let value_ty = ty.value.subst_bound_vars(&assoc_ty_bound_vars(&generic_params));
+    // FIXME: Chalk's `AssociatedTyValueBound` has no where clauses, so where
+    // clauses on the type alias itself can't be passed on here; the where
+    // clauses of the trait's associated type still apply.
+    let value_bound = rust_ir::AssociatedTyValueBound { ty: value_ty.to_chalk(db) };
     let value = rust_ir::AssociatedTyValue {
         impl_id: Impl::ImplDef(impl_id).to_chalk(db),
         associated_ty_id: assoc_ty.to_chalk(db),
@@ -533,7 +1050,7 @@ pub(crate) fn fn_def_datum_query(
     let callable_def: CallableDef = from_chalk(db, fn_def_id);
     let generic_params = generics(db.upcast(), callable_def.into());
     let sig = db.callable_item_signature(callable_def);
-    let bound_vars = Substs::bound_vars(&generic_params, DebruijnIndex::INNERMOST);
+    let bound_vars = Substs::for_generics(&generic_params);
     let where_clauses = convert_where_clauses(db, callable_def.into(), &bound_vars);
     let bound = rust_ir::FnDefDatumBound {
         // Note: Chalk doesn't actually use this information yet as far as I am aware, but we provide it anyway
@@ -552,11 +1069,23 @@ pub(crate) fn fn_def_datum_query(
         ),
         where_clauses,
     };
-    let datum =
-        FnDefDatum { id: fn_def_id, binders: make_binders(bound, sig.num_binders), abi: () };
+    // struct and enum variant constructors always use the Rust ABI
+    let abi = match callable_def {
+        CallableDef::FunctionId(f) => FnAbi::from_abi_str(db.function_data(f).abi.as_deref()),
+        CallableDef::StructId(_) | CallableDef::EnumVariantId(_) => FnAbi::Rust,
+    };
+    // FIXME: Chalk's `FnDefDatum` can't express safety or C-variadic
+    // parameters yet, so whether the function is `unsafe` (or has
+    // `#[target_feature]`, making it unsafe to call) is only known to the
+    // unsafe checker. The `...` of a variadic function is at least left out
+    // of the argument types.
+    let datum = FnDefDatum { id: fn_def_id, binders: make_binders(bound, sig.num_binders), abi };
     Arc::new(datum)
 }
 
+// Chalk's `AdtId` wraps an interned `TypeCtor`, not a `hir_def::AdtId`: fn
+// pointers and closures are represented as ADTs too. So getting at the
+// `hir_def::AdtId` always needs the database to look up the interned type ctor.
 impl From<AdtId> for crate::TypeCtorId {
     fn from(struct_id: AdtId) -> Self {
         struct_id.0
@@ -605,6 +1134,18 @@ impl From<crate::db::InternedOpaqueTyId> for OpaqueTyId {
     }
 }
 
+impl From<ClosureId> for crate::db::InternedClosureId {
+    fn from(id: ClosureId) -> Self {
+        InternKey::from_intern_id(id.0)
+    }
+}
+
+impl From<crate::db::InternedClosureId> for ClosureId {
+    fn from(id: crate::db::InternedClosureId) -> Self {
+        chalk_ir::ClosureId(id.as_intern_id())
+    }
+}
+
 impl From<rust_ir::AssociatedTyValueId<Interner>> for crate::traits::AssocTyValueId {
     fn from(id: rust_ir::AssociatedTyValueId<Interner>) -> Self {
         Self::from_intern_id(id.0)
@@ -24,6 +24,27 @@ pub type FnDefId = chalk_ir::FnDefId<Interner>;
 pub type FnDefDatum = chalk_solve::rust_ir::FnDefDatum<Interner>;
 pub type OpaqueTyId = chalk_ir::OpaqueTyId<Interner>;
 pub type OpaqueTyDatum = chalk_solve::rust_ir::OpaqueTyDatum<Interner>;
+pub type ClosureId = chalk_ir::ClosureId<Interner>;
+
+/// The calling convention of a function. We only tell Chalk about the ABIs
+/// it might need to tell apart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FnAbi {
+    Rust,
+    C,
+    Other,
+}
+
+impl FnAbi {
+    /// From the ABI string of an `extern "abi"`; `None` means no `extern`.
+    pub fn from_abi_str(abi: Option<&str>) -> FnAbi {
+        match abi {
+            None | Some("Rust") => FnAbi::Rust,
+            Some("C") => FnAbi::C,
+            Some(_) => FnAbi::Other,
+        }
+    }
+}
 
 impl chalk_ir::interner::Interner for Interner {
     type InternedType = Box<chalk_ir::TyData<Self>>; // FIXME use Arc?
@@ -43,7 +64,7 @@ impl chalk_ir::interner::Interner for Interner {
     type DefId = InternId;
     type InternedAdtId = crate::TypeCtorId;
     type Identifier = TypeAliasId;
-    type FnAbi = ();
+    type FnAbi = FnAbi;
 
     fn debug_adt_id(type_kind_id: AdtId, fmt: &mut fmt::Formatter<'_>) -> Option<fmt::Result> {
         tls::with_current_program(|prog| Some(prog?.debug_struct_id(type_kind_id, fmt)))
@@ -9,14 +9,17 @@ use chalk_ir::{
 };
 use chalk_solve::rust_ir;
 
-use hir_def::{type_ref::Mutability, AssocContainerId, GenericDefId, Lookup, TypeAliasId};
+use hir_def::{
+    expr::ExprId, type_ref::Mutability, AssocContainerId, DefWithBodyId, EnumVariantId, FunctionId,
+    GenericDefId, Lookup, StructId, TypeAliasId,
+};
 use ra_db::salsa::InternKey;
 
 use crate::{
     db::HirDatabase,
     primitive::{FloatBitness, FloatTy, IntBitness, IntTy, Signedness},
     traits::{builtin, AssocTyValue, Canonical, Impl, Obligation},
-    ApplicationTy, CallableDef, GenericPredicate, InEnvironment, OpaqueTy, OpaqueTyId,
+    ApplicationTy, CallableDef, GenericPredicate, InEnvironment, Lifetime, OpaqueTy, OpaqueTyId,
     ProjectionPredicate, ProjectionTy, Substs, TraitEnvironment, TraitRef, Ty, TyKind, TypeCtor,
 };
 
@@ -41,16 +44,9 @@ impl ToChalk for Ty {
                     chalk_ir::ApplicationTy { name, substitution }.cast(&Interner).intern(&Interner)
                 }
             },
-            Ty::Projection(proj_ty) => {
-                let associated_ty_id = proj_ty.associated_ty.to_chalk(db);
-                let substitution = proj_ty.parameters.to_chalk(db);
-                chalk_ir::AliasTy::Projection(chalk_ir::ProjectionTy {
-                    associated_ty_id,
-                    substitution,
-                })
+            Ty::Projection(proj_ty) => chalk_ir::AliasTy::Projection(proj_ty.to_chalk(db))
                 .cast(&Interner)
-                .intern(&Interner)
-            }
+                .intern(&Interner),
             Ty::Placeholder(id) => {
                 let interned_id = db.intern_type_param_id(id);
                 PlaceholderIndex {
@@ -108,9 +104,7 @@ impl ToChalk for Ty {
                 Ty::Placeholder(db.lookup_intern_type_param_id(interned_id))
             }
             chalk_ir::TyData::Alias(chalk_ir::AliasTy::Projection(proj)) => {
-                let associated_ty = from_chalk(db, proj.associated_ty_id);
-                let parameters = from_chalk(db, proj.substitution);
-                Ty::Projection(ProjectionTy { associated_ty, parameters })
+                Ty::Projection(from_chalk(db, proj))
             }
             chalk_ir::TyData::Alias(chalk_ir::AliasTy::Opaque(opaque_ty)) => {
                 let impl_trait_id = from_chalk(db, opaque_ty.opaque_ty_id);
@@ -147,6 +141,53 @@ impl ToChalk for Ty {
 const FAKE_PLACEHOLDER: PlaceholderIndex =
     PlaceholderIndex { ui: UniverseIndex::ROOT, idx: usize::MAX };
 
+/// We represent `'static` with another reserved placeholder, since Chalk
+/// doesn't know about it.
+const STATIC_PLACEHOLDER: PlaceholderIndex =
+    PlaceholderIndex { ui: UniverseIndex::ROOT, idx: usize::MAX - 1 };
+
+impl ToChalk for Lifetime {
+    type Chalk = chalk_ir::Lifetime<Interner>;
+
+    fn to_chalk(self, db: &dyn HirDatabase) -> chalk_ir::Lifetime<Interner> {
+        match self {
+            Lifetime::Static => STATIC_PLACEHOLDER.to_lifetime(&Interner),
+            Lifetime::Anonymous => FAKE_PLACEHOLDER.to_lifetime(&Interner),
+            Lifetime::Named(def, name) => {
+                let interned_id = db.intern_lifetime_param((def, name));
+                PlaceholderIndex {
+                    ui: UniverseIndex::ROOT,
+                    idx: interned_id.as_intern_id().as_usize(),
+                }
+                .to_lifetime(&Interner)
+            }
+            Lifetime::Bound(idx) => chalk_ir::LifetimeData::BoundVar(idx).intern(&Interner),
+        }
+    }
+
+    fn from_chalk(db: &dyn HirDatabase, lifetime: chalk_ir::Lifetime<Interner>) -> Lifetime {
+        match lifetime.data(&Interner) {
+            chalk_ir::LifetimeData::Placeholder(idx) if *idx == STATIC_PLACEHOLDER => {
+                Lifetime::Static
+            }
+            chalk_ir::LifetimeData::Placeholder(idx) if *idx == FAKE_PLACEHOLDER => {
+                Lifetime::Anonymous
+            }
+            chalk_ir::LifetimeData::Placeholder(idx) => {
+                assert_eq!(idx.ui, UniverseIndex::ROOT);
+                let interned_id = crate::db::InternedLifetimeParam::from_intern_id(
+                    crate::salsa::InternId::from(idx.idx),
+                );
+                let (def, name) = db.lookup_intern_lifetime_param(interned_id);
+                Lifetime::Named(def, name)
+            }
+            chalk_ir::LifetimeData::BoundVar(idx) => Lifetime::Bound(*idx),
+            // we don't track any other lifetimes, like inference variables
+            _ => Lifetime::Anonymous,
+        }
+    }
+}
+
 /// We currently don't model lifetimes, but Chalk does. So, we have to insert a
 /// fake lifetime here, because Chalks built-in logic may expect it to be there.
 fn ref_to_chalk(
@@ -255,6 +296,19 @@ impl ToChalk for hir_def::TraitId {
     }
 }
 
+/// Closures are identified by the body they're in and their expression.
+impl ToChalk for (DefWithBodyId, ExprId) {
+    type Chalk = ClosureId;
+
+    fn to_chalk(self, db: &dyn HirDatabase) -> ClosureId {
+        db.intern_closure(self).into()
+    }
+
+    fn from_chalk(db: &dyn HirDatabase, closure_id: ClosureId) -> (DefWithBodyId, ExprId) {
+        db.lookup_intern_closure(closure_id.into())
+    }
+}
+
 impl ToChalk for OpaqueTyId {
     type Chalk = chalk_ir::OpaqueTyId<Interner>;
 
@@ -453,6 +507,31 @@ impl ToChalk for CallableDef {
     }
 }
 
+/// Shortcuts for when we know which kind of `CallableDef` we're dealing
+/// with. Converting back panics if the `FnDefId` is of a different kind.
+macro_rules! callable_def_to_chalk {
+    ($($id:ident),*) => {$(
+        impl ToChalk for $id {
+            type Chalk = FnDefId;
+
+            fn to_chalk(self, db: &dyn HirDatabase) -> FnDefId {
+                CallableDef::$id(self).to_chalk(db)
+            }
+
+            fn from_chalk(db: &dyn HirDatabase, fn_def_id: FnDefId) -> $id {
+                match from_chalk(db, fn_def_id) {
+                    CallableDef::$id(it) => it,
+                    def => panic!(concat!("expected ", stringify!($id), ", got {:?}"), def),
+                }
+            }
+        }
+    )*};
+}
+
+callable_def_to_chalk!(FunctionId, StructId, EnumVariantId);
+
+/// This is just a conversion between intern ids and doesn't access the
+/// database. (A `From` impl isn't possible, since neither type is local.)
 impl ToChalk for TypeAliasId {
     type Chalk = AssocTypeId;
 
@@ -502,35 +581,29 @@ impl ToChalk for GenericPredicate {
         where_clause: chalk_ir::QuantifiedWhereClause<Interner>,
     ) -> GenericPredicate {
         // we don't produce any where clauses with binders and can't currently deal with them
-        match where_clause
-            .skip_binders()
-            .shifted_out(&Interner)
-            .expect("unexpected bound vars in where clause")
-        {
+        let where_clause = match where_clause.skip_binders().shifted_out(&Interner) {
+            Ok(it) => it,
+            Err(_) => return GenericPredicate::Error,
+        };
+        match where_clause {
             chalk_ir::WhereClause::Implemented(tr) => {
                 GenericPredicate::Implemented(from_chalk(db, tr))
             }
-            chalk_ir::WhereClause::AliasEq(projection_eq) => {
-                let projection_ty = from_chalk(
-                    db,
-                    match projection_eq.alias {
-                        chalk_ir::AliasTy::Projection(p) => p,
-                        _ => unimplemented!(),
-                    },
-                );
-                let ty = from_chalk(db, projection_eq.ty);
-                GenericPredicate::Projection(ProjectionPredicate { projection_ty, ty })
-            }
-
-            chalk_ir::WhereClause::LifetimeOutlives(_) => {
-                // we shouldn't get these from Chalk
-                panic!("encountered LifetimeOutlives from Chalk")
-            }
-
-            chalk_ir::WhereClause::TypeOutlives(_) => {
-                // we shouldn't get these from Chalk
-                panic!("encountered TypeOutlives from Chalk")
-            }
+            chalk_ir::WhereClause::AliasEq(chalk_ir::AliasEq {
+                alias: chalk_ir::AliasTy::Projection(projection_ty),
+                ty,
+            }) => GenericPredicate::Projection(ProjectionPredicate {
+                projection_ty: from_chalk(db, projection_ty),
+                ty: from_chalk(db, ty),
+            }),
+            // we can't express these as predicates, and don't use them for
+            // anything anyway
+            chalk_ir::WhereClause::AliasEq(chalk_ir::AliasEq {
+                alias: chalk_ir::AliasTy::Opaque(_),
+                ..
+            })
+            | chalk_ir::WhereClause::LifetimeOutlives(_)
+            | chalk_ir::WhereClause::TypeOutlives(_) => GenericPredicate::Error,
         }
     }
 }
@@ -539,9 +612,13 @@ impl ToChalk for ProjectionTy {
     type Chalk = chalk_ir::ProjectionTy<Interner>;
 
     fn to_chalk(self, db: &dyn HirDatabase) -> chalk_ir::ProjectionTy<Interner> {
+        // Chalk wants the associated type's own parameters first
+        let parent_len = assoc_ty_parent_len(db, self.associated_ty);
+        let (parent, own) = self.parameters.split_at(parent_len.min(self.parameters.len()));
+        let parameters = Substs(own.iter().chain(parent).cloned().collect());
         chalk_ir::ProjectionTy {
             associated_ty_id: self.associated_ty.to_chalk(db),
-            substitution: self.parameters.to_chalk(db),
+            substitution: parameters.to_chalk(db),
         }
     }
 
@@ -549,9 +626,13 @@ impl ToChalk for ProjectionTy {
         db: &dyn HirDatabase,
         projection_ty: chalk_ir::ProjectionTy<Interner>,
     ) -> ProjectionTy {
+        let associated_ty: TypeAliasId = from_chalk(db, projection_ty.associated_ty_id);
+        let parameters: Substs = from_chalk(db, projection_ty.substitution);
+        let parent_len = assoc_ty_parent_len(db, associated_ty);
+        let (own, parent) = parameters.split_at(parameters.len().saturating_sub(parent_len));
         ProjectionTy {
-            associated_ty: from_chalk(db, projection_ty.associated_ty_id),
-            parameters: from_chalk(db, projection_ty.substitution),
+            associated_ty,
+            parameters: Substs(parent.iter().chain(own).cloned().collect()),
         }
     }
 }
@@ -637,24 +718,46 @@ impl ToChalk for Arc<TraitEnvironment> {
     type Chalk = chalk_ir::Environment<Interner>;
 
     fn to_chalk(self, db: &dyn HirDatabase) -> chalk_ir::Environment<Interner> {
-        let mut clauses = Vec::new();
-        for pred in &self.predicates {
+        let mut builder = ChalkEnvironmentBuilder::new(db);
+        builder.add_explicit_bounds(&self.predicates);
+        builder.build()
+    }
+
+    fn from_chalk(
+        _db: &dyn HirDatabase,
+        _env: chalk_ir::Environment<Interner>,
+    ) -> Arc<TraitEnvironment> {
+        unimplemented!()
+    }
+}
+
+/// Collects the clauses we assume to hold in some environment.
+pub(super) struct ChalkEnvironmentBuilder<'a> {
+    db: &'a dyn HirDatabase,
+    clauses: Vec<chalk_ir::ProgramClause<Interner>>,
+}
+
+impl<'a> ChalkEnvironmentBuilder<'a> {
+    pub(super) fn new(db: &'a dyn HirDatabase) -> ChalkEnvironmentBuilder<'a> {
+        ChalkEnvironmentBuilder { db, clauses: Vec::new() }
+    }
+
+    /// Adds where clauses that are declared explicitly.
+    pub(super) fn add_explicit_bounds(&mut self, bounds: &[GenericPredicate]) {
+        for pred in bounds {
             if pred.is_error() {
                 // for env, we just ignore errors
                 continue;
             }
             let program_clause: chalk_ir::ProgramClause<Interner> =
-                pred.clone().to_chalk(db).cast(&Interner);
-            clauses.push(program_clause.into_from_env_clause(&Interner));
+                pred.clone().to_chalk(self.db).cast(&Interner);
+            let clause = program_clause.into_from_env_clause(&Interner);
+            self.clauses.push(clause);
         }
-        chalk_ir::Environment::new(&Interner).add_clauses(&Interner, clauses)
     }
 
-    fn from_chalk(
-        _db: &dyn HirDatabase,
-        _env: chalk_ir::Environment<Interner>,
-    ) -> Arc<TraitEnvironment> {
-        unimplemented!()
+    pub(super) fn build(&self) -> chalk_ir::Environment<Interner> {
+        chalk_ir::Environment::new(&Interner).add_clauses(&Interner, self.clauses.iter().cloned())
     }
 }
 
@@ -728,7 +831,19 @@ impl ToChalk for builtin::BuiltinImplAssocTyValueData {
     }
 }
 
-pub(super) fn make_binders<T>(value: T, num_vars: usize) -> chalk_ir::Binders<T>
+/// The number of parameters of the trait or impl containing the associated type.
+/// Traits and impls don't have parents themselves, so we can take their own
+/// parameters instead of building the full `Generics`.
+fn assoc_ty_parent_len(db: &dyn HirDatabase, type_alias: TypeAliasId) -> usize {
+    let parent: GenericDefId = match type_alias.lookup(db.upcast()).container {
+        AssocContainerId::TraitId(it) => it.into(),
+        AssocContainerId::ImplId(it) => it.into(),
+        AssocContainerId::ContainerId(_) => return 0,
+    };
+    db.generic_params(parent).types.len()
+}
+
+pub(crate) fn make_binders<T>(value: T, num_vars: usize) -> chalk_ir::Binders<T>
 where
     T: HasInterner<Interner = Interner>,
 {
@@ -788,7 +903,12 @@ pub(super) fn generic_predicate_to_inline_bound(
                 AssocContainerId::TraitId(t) => t,
                 _ => panic!("associated type not in trait"),
             };
-            let args_no_self = proj.projection_ty.parameters[1..]
+            let parent_len = assoc_ty_parent_len(db, proj.projection_ty.associated_ty);
+            let args_no_self = proj.projection_ty.parameters[1..parent_len]
+                .iter()
+                .map(|ty| ty.clone().to_chalk(db).cast(&Interner))
+                .collect();
+            let parameters = proj.projection_ty.parameters[parent_len..]
                 .iter()
                 .map(|ty| ty.clone().to_chalk(db).cast(&Interner))
                 .collect();
@@ -796,7 +916,7 @@ pub(super) fn generic_predicate_to_inline_bound(
                 value: proj.ty.clone().to_chalk(db),
                 trait_bound: rust_ir::TraitBound { trait_id: trait_.to_chalk(db), args_no_self },
                 associated_ty_id: proj.projection_ty.associated_ty.to_chalk(db),
-                parameters: Vec::new(), // FIXME we don't support generic associated types yet
+                parameters,
             };
             Some(rust_ir::InlineBound::AliasEqBound(alias_eq_bound))
         }
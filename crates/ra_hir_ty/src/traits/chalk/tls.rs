@@ -73,6 +73,9 @@ impl DebugContext<'_> {
                 crate::OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
                     write!(f, "{{impl trait {} of {:?}}}", idx, func)?;
                 }
+                crate::OpaqueTyId::AssocTyImplTrait(type_alias) => {
+                    write!(f, "{{impl trait of {:?}}}", type_alias)?;
+                }
             },
             TypeCtor::Closure { def, expr } => {
                 write!(f, "{{closure {:?} in ", expr.into_raw())?;
@@ -157,7 +160,11 @@ impl DebugContext<'_> {
             _ => panic!("associated type not in trait"),
         };
         let trait_data = self.0.trait_data(trait_);
-        let params = projection_ty.substitution.as_slice(&Interner);
+        let trait_params_len = self.0.generic_params(trait_.into()).types.len();
+        // Chalk puts the associated type's own parameters first
+        let substitution = projection_ty.substitution.as_slice(&Interner);
+        let (own_params, params) =
+            substitution.split_at(substitution.len().saturating_sub(trait_params_len));
         write!(fmt, "<{:?} as {}", &params[0], trait_data.name,)?;
         if params.len() > 1 {
             write!(
@@ -166,7 +173,15 @@ impl DebugContext<'_> {
                 &params[1..].iter().format_with(", ", |x, f| f(&format_args!("{:?}", x))),
             )?;
         }
-        write!(fmt, ">::{}", type_alias_data.name)
+        write!(fmt, ">::{}", type_alias_data.name)?;
+        if !own_params.is_empty() {
+            write!(
+                fmt,
+                "<{}>",
+                own_params.iter().format_with(", ", |x, f| f(&format_args!("{:?}", x))),
+            )?;
+        }
+        Ok(())
     }
 
     pub fn debug_opaque_ty(
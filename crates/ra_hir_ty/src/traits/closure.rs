@@ -0,0 +1,256 @@
+//! Works out how a closure uses the local variables it captures from its
+//! surrounding body. This determines which of the `Fn` traits it implements,
+//! and what its upvars look like.
+//!
+//! Chalk also asks for this while solving goals that come up during inference
+//! of the surrounding body, when we can't use the result of that inference
+//! yet. Then we only look at the body itself, so we can't tell which captured
+//! values are `Copy` or how method receivers are borrowed, and assume the
+//! closure only borrows them.
+use std::sync::Arc;
+
+use hir_def::{
+    body::{scope::ExprScopes, Body},
+    expr::{BinaryOp, Expr, ExprId, UnaryOp},
+    lang_item::LangItemTarget,
+    resolver::HasResolver,
+    type_ref::Mutability,
+    DefWithBodyId, HasModule, PatId,
+};
+use hir_expand::name::Name;
+use ra_db::CrateId;
+use rustc_hash::FxHashSet;
+
+use super::{FnTrait, InEnvironment, Obligation, TraitEnvironment};
+use crate::{
+    db::HirDatabase, infer::is_being_inferred, ApplicationTy, Canonical, InferenceResult, Substs,
+    TraitRef, Ty, TypeCtor,
+};
+
+/// How a closure needs to capture a variable, from least to most demanding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum CaptureKind {
+    ByRef,
+    ByMutRef,
+    ByValue,
+}
+
+/// The most specific of the `Fn` traits that the closure implements.
+pub(crate) fn closure_kind(db: &dyn HirDatabase, def: DefWithBodyId, closure: ExprId) -> FnTrait {
+    let captures = closure_captures(db, def, closure);
+    match captures.iter().map(|(_, kind)| *kind).max() {
+        Some(CaptureKind::ByValue) => FnTrait::FnOnce,
+        Some(CaptureKind::ByMutRef) => FnTrait::FnMut,
+        Some(CaptureKind::ByRef) | None => FnTrait::Fn,
+    }
+}
+
+/// The tuple of the types the closure stores its captures as.
+pub(crate) fn closure_upvars_ty(db: &dyn HirDatabase, def: DefWithBodyId, closure: ExprId) -> Ty {
+    let infer = infer_if_possible(db, def);
+    let tys: Vec<Ty> = closure_captures(db, def, closure)
+        .into_iter()
+        .map(|(pat, kind)| {
+            let ty = infer.as_ref().map_or(Ty::Unknown, |infer| infer[pat].clone());
+            match kind {
+                CaptureKind::ByRef => Ty::apply_one(TypeCtor::Ref(Mutability::Shared), ty),
+                CaptureKind::ByMutRef => Ty::apply_one(TypeCtor::Ref(Mutability::Mut), ty),
+                CaptureKind::ByValue => ty,
+            }
+        })
+        .collect();
+    Ty::apply(TypeCtor::Tuple { cardinality: tys.len() as u16 }, Substs(tys.into()))
+}
+
+/// The local variables of the surrounding body that the closure mentions, in
+/// order of first use, along with how it needs to capture them.
+pub(crate) fn closure_captures(
+    db: &dyn HirDatabase,
+    def: DefWithBodyId,
+    closure: ExprId,
+) -> Vec<(PatId, CaptureKind)> {
+    let body = db.body(def);
+    let closure_body = match &body[closure] {
+        Expr::Lambda { body, .. } => *body,
+        _ => return Vec::new(),
+    };
+    let scopes = db.expr_scopes(def);
+    // everything that's in scope at the closure itself lives outside of it
+    let outer_pats = scopes
+        .scope_chain(scopes.scope_for(closure))
+        .flat_map(|scope| scopes.entries(scope))
+        .map(|entry| entry.pat())
+        .collect();
+    let infer = infer_if_possible(db, def);
+    let mut collector = CaptureCollector {
+        db,
+        krate: def.module(db.upcast()).krate,
+        env: TraitEnvironment::lower(db, &def.resolver(db.upcast())),
+        body: &body,
+        scopes: &scopes,
+        infer: infer.as_deref(),
+        outer_pats,
+        captures: Vec::new(),
+    };
+    collector.walk(closure_body, CaptureKind::ByValue);
+    collector.captures
+}
+
+/// The inference result of `def`, unless we're in the middle of inferring it.
+fn infer_if_possible(db: &dyn HirDatabase, def: DefWithBodyId) -> Option<Arc<InferenceResult>> {
+    if is_being_inferred(def) {
+        None
+    } else {
+        Some(db.infer(def))
+    }
+}
+
+/// Places behind a deref or index can't be moved out of, only (re)borrowed.
+fn borrow_only(kind: CaptureKind) -> CaptureKind {
+    match kind {
+        CaptureKind::ByMutRef => CaptureKind::ByMutRef,
+        CaptureKind::ByRef | CaptureKind::ByValue => CaptureKind::ByRef,
+    }
+}
+
+struct CaptureCollector<'a> {
+    db: &'a dyn HirDatabase,
+    krate: CrateId,
+    env: Arc<TraitEnvironment>,
+    body: &'a Body,
+    scopes: &'a ExprScopes,
+    infer: Option<&'a InferenceResult>,
+    outer_pats: FxHashSet<PatId>,
+    captures: Vec<(PatId, CaptureKind)>,
+}
+
+impl CaptureCollector<'_> {
+    /// Walks `expr`, which is used in the way described by `kind`.
+    fn walk(&mut self, expr: ExprId, kind: CaptureKind) {
+        let body = self.body;
+        match &body[expr] {
+            Expr::Path(path) => {
+                if let Some(pat) = self.captured_pat(expr, path.mod_path().as_ident()) {
+                    let ty = self.infer.map_or(Ty::Unknown, |infer| infer[pat].clone());
+                    let kind = self.copy_to_ref(kind, &ty);
+                    self.add_capture(pat, kind);
+                }
+            }
+            Expr::Ref { expr: inner, mutability, .. } => {
+                let kind = match mutability {
+                    Mutability::Shared => CaptureKind::ByRef,
+                    Mutability::Mut => CaptureKind::ByMutRef,
+                };
+                self.walk(*inner, kind)
+            }
+            Expr::Field { expr: inner, .. } => {
+                let ty = self.infer.map_or(Ty::Unknown, |infer| infer[expr].clone());
+                let kind = self.copy_to_ref(kind, &ty);
+                self.walk(*inner, kind)
+            }
+            Expr::UnaryOp { expr: inner, op: UnaryOp::Deref } => {
+                // moving out of a deref isn't allowed, so this is at most a borrow
+                self.walk(*inner, borrow_only(kind))
+            }
+            Expr::Index { base, index } => {
+                self.walk(*base, borrow_only(kind));
+                self.walk(*index, CaptureKind::ByValue);
+            }
+            Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::Assignment { .. }) } => {
+                self.walk(*lhs, CaptureKind::ByMutRef);
+                self.walk(*rhs, CaptureKind::ByValue);
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                self.walk(*receiver, self.receiver_kind(expr));
+                for arg in args {
+                    self.walk(*arg, CaptureKind::ByValue);
+                }
+            }
+            Expr::Match { expr: scrutinee, arms } => {
+                // FIXME: this depends on the binding modes of the arms
+                self.walk(*scrutinee, CaptureKind::ByRef);
+                for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        self.walk(guard, CaptureKind::ByValue);
+                    }
+                    self.walk(arm.expr, CaptureKind::ByValue);
+                }
+            }
+            e => e.walk_child_exprs(|child| self.walk(child, CaptureKind::ByValue)),
+        }
+    }
+
+    fn captured_pat(&self, expr: ExprId, name: Option<&Name>) -> Option<PatId> {
+        let scope = self.scopes.scope_for(expr)?;
+        let entry = self.scopes.resolve_name_in_scope(scope, name?)?;
+        if self.outer_pats.contains(&entry.pat()) {
+            Some(entry.pat())
+        } else {
+            None
+        }
+    }
+
+    fn add_capture(&mut self, pat: PatId, kind: CaptureKind) {
+        match self.captures.iter_mut().find(|(it, _)| *it == pat) {
+            Some((_, existing)) => *existing = (*existing).max(kind),
+            None => self.captures.push((pat, kind)),
+        }
+    }
+
+    /// Using a `Copy` value by value only needs a shared borrow.
+    fn copy_to_ref(&self, kind: CaptureKind, ty: &Ty) -> CaptureKind {
+        if kind == CaptureKind::ByValue && self.is_copy(ty) {
+            CaptureKind::ByRef
+        } else {
+            kind
+        }
+    }
+
+    fn receiver_kind(&self, method_call: ExprId) -> CaptureKind {
+        let func = match self.infer.and_then(|infer| infer.method_resolution(method_call)) {
+            Some(it) => it,
+            None => return CaptureKind::ByRef,
+        };
+        let sig = self.db.callable_item_signature(func.into());
+        match sig.value.params().first() {
+            Some(Ty::Apply(ApplicationTy { ctor: TypeCtor::Ref(Mutability::Shared), .. })) => {
+                CaptureKind::ByRef
+            }
+            Some(Ty::Apply(ApplicationTy { ctor: TypeCtor::Ref(Mutability::Mut), .. })) => {
+                CaptureKind::ByMutRef
+            }
+            _ => CaptureKind::ByValue,
+        }
+    }
+
+    fn is_copy(&self, ty: &Ty) -> bool {
+        match ty {
+            // these are `Copy` even without a `core` to tell us so
+            Ty::Apply(ApplicationTy { ctor, .. }) => match ctor {
+                TypeCtor::Bool
+                | TypeCtor::Char
+                | TypeCtor::Int(_)
+                | TypeCtor::Float(_)
+                | TypeCtor::Never
+                | TypeCtor::RawPtr(_)
+                | TypeCtor::Ref(Mutability::Shared)
+                | TypeCtor::FnPtr { .. }
+                | TypeCtor::FnDef(_) => return true,
+                _ => {}
+            },
+            // don't make up errors for things we don't know
+            Ty::Unknown => return true,
+            _ => {}
+        }
+        let copy_trait = match self.db.lang_item(self.krate, "copy".into()) {
+            Some(LangItemTarget::TraitId(it)) => it,
+            _ => return false,
+        };
+        let trait_ref = TraitRef { trait_: copy_trait, substs: Substs::single(ty.clone()) };
+        let goal = Canonical::new(
+            InEnvironment::new(self.env.clone(), Obligation::Trait(trait_ref)),
+            std::iter::empty(),
+        );
+        self.db.trait_solve(self.krate, goal).is_some()
+    }
+}
@@ -0,0 +1,127 @@
+//! Checks whether a trait can be used as the principal trait of a trait object,
+//! and if not, why.
+use std::sync::Arc;
+
+use hir_def::{
+    lang_item::LangItemTarget, AssocItemId, ConstId, FunctionId, GenericDefId, HasModule, Lookup,
+    TraitId, TypeAliasId,
+};
+
+use crate::{
+    db::HirDatabase, utils::generics, BoundVar, DebruijnIndex, GenericPredicate, Ty, TypeWalk,
+};
+
+/// A reason why a trait is not object safe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectSafetyViolation {
+    /// The trait requires `Self: Sized`.
+    SizedSelf,
+    /// An associated function without a receiver.
+    StaticMethod(FunctionId),
+    /// A method with type parameters.
+    GenericMethod(FunctionId),
+    /// A method that mentions `Self` outside of the receiver.
+    SelfInSignature(FunctionId),
+    /// A method returning `impl Trait`.
+    ReturnImplTrait(FunctionId),
+    /// An associated const.
+    AssocConst(ConstId),
+    /// An associated type with generic parameters.
+    GenericAssocType(TypeAliasId),
+}
+
+pub(crate) fn object_safety_violations_query(
+    db: &dyn HirDatabase,
+    trait_: TraitId,
+) -> Arc<[ObjectSafetyViolation]> {
+    let mut violations = Vec::new();
+    if requires_sized_self(db, trait_, trait_.into()) {
+        violations.push(ObjectSafetyViolation::SizedSelf);
+    }
+    for (_, item) in db.trait_data(trait_).items.iter() {
+        match *item {
+            AssocItemId::FunctionId(func) => {
+                // methods with `where Self: Sized` can't be called on trait
+                // objects, so they don't matter
+                if requires_sized_self(db, trait_, func.into()) {
+                    continue;
+                }
+                violations.extend(method_violation(db, func));
+            }
+            AssocItemId::ConstId(konst) => {
+                violations.push(ObjectSafetyViolation::AssocConst(konst))
+            }
+            AssocItemId::TypeAliasId(type_alias) => {
+                let (_total, _parent, own) = generics(db.upcast(), type_alias.into()).len_split();
+                if own > 0 {
+                    violations.push(ObjectSafetyViolation::GenericAssocType(type_alias));
+                }
+            }
+        }
+    }
+    violations.into()
+}
+
+fn method_violation(db: &dyn HirDatabase, func: FunctionId) -> Option<ObjectSafetyViolation> {
+    if !db.function_data(func).has_self_param {
+        return Some(ObjectSafetyViolation::StaticMethod(func));
+    }
+    let (_total, _parent, own) = generics(db.upcast(), func.into()).len_split();
+    if own > 0 {
+        return Some(ObjectSafetyViolation::GenericMethod(func));
+    }
+    if db.return_type_impl_traits(func).is_some() {
+        return Some(ObjectSafetyViolation::ReturnImplTrait(func));
+    }
+    let sig = db.callable_item_signature(func.into());
+    let mentions_self = sig.value.params()[1..]
+        .iter()
+        .chain(std::iter::once(sig.value.ret()))
+        .any(|ty| mentions_self_ty(ty.clone()));
+    if mentions_self {
+        return Some(ObjectSafetyViolation::SelfInSignature(func));
+    }
+    None
+}
+
+/// The trait's `Self` is always the first parameter of the generics of the
+/// trait and its items.
+fn mentions_self_ty(mut ty: Ty) -> bool {
+    // projections like `Self::Item` are fine, since the trait object type
+    // has to specify them
+    ty.walk_mut_binders(
+        &mut |ty, binders| {
+            if let Ty::Projection(proj) = ty {
+                if proj.parameters.first() == Some(&Ty::Bound(BoundVar::new(binders, 0))) {
+                    *ty = Ty::Unknown;
+                }
+            }
+        },
+        DebruijnIndex::INNERMOST,
+    );
+    let mut found = false;
+    ty.walk_mut_binders(
+        &mut |ty, binders| {
+            if let Ty::Bound(bv) = ty {
+                if *bv == BoundVar::new(binders, 0) {
+                    found = true;
+                }
+            }
+        },
+        DebruijnIndex::INNERMOST,
+    );
+    found
+}
+
+fn requires_sized_self(db: &dyn HirDatabase, trait_: TraitId, def: GenericDefId) -> bool {
+    let krate = trait_.lookup(db.upcast()).container.module(db.upcast()).krate;
+    let sized_trait = match db.lang_item(krate, "sized".into()) {
+        Some(LangItemTarget::TraitId(it)) => it,
+        _ => return false,
+    };
+    let self_ty = Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0));
+    db.generic_predicates(def).iter().any(|pred| match &pred.value {
+        GenericPredicate::Implemented(tr) => tr.trait_ == sized_trait && tr.self_ty() == &self_ty,
+        _ => false,
+    })
+}
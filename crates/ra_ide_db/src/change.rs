@@ -238,14 +238,19 @@ impl RootDatabase {
             hir::db::GenericPredicatesForParamQuery
             hir::db::GenericPredicatesQuery
             hir::db::GenericDefaultsQuery
+            hir::db::TypeCtorFlagsQuery
             hir::db::InherentImplsInCrateQuery
             hir::db::TraitImplsInCrateQuery
             hir::db::TraitImplsInDepsQuery
+            hir::db::TraitImplsInDepsForQuery
+            hir::db::AllExplicitImplsInKrateQuery
             hir::db::AssociatedTyDataQuery
             hir::db::TraitDatumQuery
             hir::db::StructDatumQuery
             hir::db::ImplDatumQuery
+            hir::db::OpaqueTyDatumQuery
             hir::db::AssociatedTyValueQuery
+            hir::db::ObjectSafetyViolationsQuery
             hir::db::TraitSolveQuery
             hir::db::ReturnTypeImplTraitsQuery
 
@@ -281,6 +286,8 @@ impl RootDatabase {
             // HirDatabase
             hir::db::InternTypeCtorQuery
             hir::db::InternTypeParamIdQuery
+            hir::db::InternLifetimeParamQuery
+            hir::db::InternClosureQuery
             hir::db::InternChalkImplQuery
             hir::db::InternAssocTyValueQuery
         ];